@@ -0,0 +1,47 @@
+use chess::{
+    piece::Side,
+    search::{Search, SearchOptions},
+    time::{Limits, Stop},
+    Board,
+};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+//
+
+/// nodes per second of the lazy smp search with an increasing thread count
+///
+/// `cargo bench --bench smp`
+fn main() {
+    let board = Board::starting();
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut base = None;
+    for threads in [1, 2, 4, 8, 16, 32].into_iter().filter(|&t| t <= cores) {
+        let search = Search::new(SearchOptions {
+            threads,
+            hash: 64,
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+        let result = search.go(
+            &board,
+            Side::White,
+            Limits::move_time(Duration::from_secs(3)),
+            Stop::new(),
+            |_| {},
+        );
+        let nps = result.nodes as f64 / start.elapsed().as_secs_f64();
+        let base = *base.get_or_insert(nps);
+
+        println!(
+            "threads: {threads:>2}  depth: {:>2}  nodes: {:>10}  nps: {nps:>10.0}  scaling: {:.2}x",
+            result.depth,
+            result.nodes,
+            nps / base,
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use std::process::exit;
+
+//
+
+/// engine matches, see [`chess::tournament::run`]
+fn main() {
+    env_logger::init();
+
+    if let Err(err) = chess::tournament::run(std::env::args().skip(1)) {
+        eprintln!("{err}");
+        exit(1);
+    }
+}
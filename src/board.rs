@@ -11,10 +11,29 @@ enum CompressedBoardPiece {
     Row,
 } */
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Board {
     // pieces: Vec<CompressedBoardPiece>,
     pieces: HashMap<BoardPos, (Side, Piece)>,
+    /// white king side, white queen side, black king side, black queen side
+    castling: [bool; 4],
+    /// the square a pawn skipped over with the last move
+    en_passant: Option<BoardPos>,
+    /// plies since the last capture or pawn move
+    halfmove: u32,
+    /// starts at 1 and goes up after every black move
+    fullmove: u32,
+    /// what [`Board::unplay`] has to restore
+    undo: Vec<Undo>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Undo {
+    moved: Option<(Side, Piece)>,
+    castling: [bool; 4],
+    en_passant: Option<BoardPos>,
+    halfmove: u32,
+    fullmove: u32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -23,47 +42,268 @@ pub struct BoardPos {
     pub rank: u8,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: BoardPos,
+    pub to: BoardPos,
+    pub promotion: Option<Piece>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    Empty,
+    Piece(char),
+    RankLength(u8),
+    RankCount,
+    Side(String),
+    Castling(String),
+    EnPassant(String),
+    Clock(String),
+    Fullmove(String),
+}
+
+/// why a position cannot be played from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionError {
+    /// kings of the side, there has to be exactly one
+    KingCount(Side, usize),
+    PawnOnBackRank(BoardPos),
+    /// the side not to move could have its king captured
+    OpponentInCheck,
+}
+
+//
+
+/// one key per (side, piece, square), one for black to move, one per
+/// castling right and one per en passant file
+static ZOBRIST: [u64; ZOBRIST_KEYS] = zobrist_keys();
+
+const ZOBRIST_KEYS: usize = 2 * 6 * 64 + 1 + 4 + 8;
+const ZOBRIST_CASTLING: usize = 2 * 6 * 64 + 1;
+const ZOBRIST_EN_PASSANT: usize = ZOBRIST_CASTLING + 4;
+
+/// king and rook squares for each castling right, in the order of
+/// [`Board::castling`]: king from, king to, rook from, rook to
+const CASTLES: [[BoardPos; 4]; 4] = [
+    [sq(5, 1), sq(7, 1), sq(8, 1), sq(6, 1)],
+    [sq(5, 1), sq(3, 1), sq(1, 1), sq(4, 1)],
+    [sq(5, 8), sq(7, 8), sq(8, 8), sq(6, 8)],
+    [sq(5, 8), sq(3, 8), sq(1, 8), sq(4, 8)],
+];
+
+const PROMOTIONS: [Option<Piece>; 4] = [
+    Some(Piece::Queen),
+    Some(Piece::Rook),
+    Some(Piece::Bishop),
+    Some(Piece::Knight),
+];
+
+const fn sq(file: u8, rank: u8) -> BoardPos {
+    BoardPos { file, rank }
+}
+
+const fn zobrist_keys() -> [u64; ZOBRIST_KEYS] {
+    // splitmix64
+    let mut keys = [0; ZOBRIST_KEYS];
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
 //
 
 impl Board {
     pub fn starting() -> Self {
-        Self::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").unwrap() // w KQkq - 0 1
+        Self::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .unwrap()
+            .0
     }
 
-    pub fn parse_fen(fen: &str) -> Option<Self> {
-        let mut pieces = HashMap::default();
-        let mut pos = BoardPos::default();
-        for c in fen.chars() {
-            let c: char = c;
-            let lc = c.to_lowercase().next().unwrap();
-            let is_lower = c == lc;
-
-            let side = if is_lower { Side::White } else { Side::Black };
+    /// parses the piece placement field of a fen string, without castling
+    /// rights or en passant
+    pub fn parse_fen(fen: &str) -> Result<Self, FenError> {
+        let placement = fen.split_whitespace().next().ok_or(FenError::Empty)?;
 
-            match lc {
-                c @ '1'..='8' => pos.file += c as u8 - b'0',
+        let mut pieces = HashMap::default();
+        let mut rank = 8;
+        let mut file = 1;
+        for c in placement.chars() {
+            match c {
+                c @ '1'..='8' => file += c as u8 - b'0',
                 '/' => {
-                    pos.rank += 1;
-                    pos.file = 1;
+                    if file != 9 {
+                        return Err(FenError::RankLength(rank));
+                    }
+                    if rank == 1 {
+                        return Err(FenError::RankCount);
+                    }
+                    rank -= 1;
+                    file = 1;
                 }
                 _ => {
-                    let piece = match lc {
-                        'r' => Some(Piece::Rook),
-                        'n' => Some(Piece::Knight),
-                        'b' => Some(Piece::Bishop),
-                        'q' => Some(Piece::Queen),
-                        'k' => Some(Piece::King),
-                        'p' => Some(Piece::Pawn),
-                        _ => None,
-                    };
-
-                    piece.map(|piece| pieces.insert(pos, (side, piece)));
-                    pos.file += 1;
+                    let (side, piece) = Piece::from_char(c).ok_or(FenError::Piece(c))?;
+                    let pos = BoardPos::new(file as i32, rank as i32)
+                        .ok_or(FenError::RankLength(rank))?;
+                    pieces.insert(pos, (side, piece));
+                    file += 1;
                 }
             };
+
+            if file > 9 {
+                return Err(FenError::RankLength(rank));
+            }
+        }
+
+        if rank != 1 {
+            return Err(FenError::RankCount);
+        }
+        if file != 9 {
+            return Err(FenError::RankLength(rank));
+        }
+
+        Ok(Self {
+            pieces,
+            castling: [false; 4],
+            en_passant: None,
+            halfmove: 0,
+            fullmove: 1,
+            undo: Vec::new(),
+        })
+    }
+
+    /// parses a full fen string, missing fields default to `w - - 0 1`
+    pub fn from_fen(fen: &str) -> Result<(Self, Side), FenError> {
+        let mut board = Self::parse_fen(fen)?;
+        let mut fields = fen.split_whitespace().skip(1);
+        let side = match fields.next() {
+            None | Some("w") => Side::White,
+            Some("b") => Side::Black,
+            Some(other) => return Err(FenError::Side(other.to_string())),
+        };
+
+        let castling = fields.next().unwrap_or("-");
+        let mut rights = [false; 4];
+        if castling != "-" {
+            for c in castling.chars() {
+                let i = "KQkq"
+                    .find(c)
+                    .ok_or_else(|| FenError::Castling(castling.to_string()))?;
+                rights[i] = true;
+            }
         }
+        board.set_castling(rights);
 
-        Some(Self { pieces })
+        board.en_passant = match fields.next() {
+            None | Some("-") => None,
+            Some(ep) => match BoardPos::parse(ep) {
+                Some(pos) if pos.rank == if side == Side::White { 6 } else { 3 } => Some(pos),
+                _ => return Err(FenError::EnPassant(ep.to_string())),
+            },
+        };
+
+        if let Some(halfmove) = fields.next() {
+            board.halfmove = halfmove
+                .parse()
+                .map_err(|_| FenError::Clock(halfmove.to_string()))?;
+        }
+        if let Some(fullmove) = fields.next() {
+            // some writers start counting at 0
+            board.fullmove = fullmove
+                .parse::<u32>()
+                .map_err(|_| FenError::Fullmove(fullmove.to_string()))?
+                .max(1);
+        }
+
+        Ok((board, side))
+    }
+
+    pub fn to_fen(&self, side: Side) -> String {
+        let mut fen = String::new();
+        for rank in (1..=8).rev() {
+            let mut empty = 0;
+            for file in 1..=8 {
+                let pos = BoardPos::new(file, rank).unwrap();
+                match self.get_piece(&pos) {
+                    Some((side, piece)) => {
+                        if empty != 0 {
+                            fen.push((b'0' + empty) as char);
+                            empty = 0;
+                        }
+                        fen.push(piece.to_char(side));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty != 0 {
+                fen.push((b'0' + empty) as char);
+            }
+            if rank != 1 {
+                fen.push('/');
+            }
+        }
+
+        let side = match side {
+            Side::White => 'w',
+            Side::Black => 'b',
+        };
+        let castling: String = "KQkq"
+            .chars()
+            .zip(self.castling)
+            .filter_map(|(c, right)| right.then_some(c))
+            .collect();
+        let castling = if castling.is_empty() { "-" } else { &castling };
+        let en_passant = self
+            .en_passant
+            .map_or_else(|| "-".to_string(), |pos| pos.to_string());
+        format!(
+            "{fen} {side} {castling} {en_passant} {} {}",
+            self.halfmove, self.fullmove
+        )
+    }
+
+    /// castling rights in `KQkq` order
+    pub fn castling(&self) -> [bool; 4] {
+        self.castling
+    }
+
+    /// sets castling rights in `KQkq` order, rights without the king and
+    /// rook on their starting squares are dropped
+    pub fn set_castling(&mut self, rights: [bool; 4]) {
+        for (i, right) in rights.into_iter().enumerate() {
+            let [king, _, rook, _] = CASTLES[i];
+            let side = if i < 2 { Side::White } else { Side::Black };
+            self.castling[i] = right
+                && self.get_piece(&king) == Some((side, Piece::King))
+                && self.get_piece(&rook) == Some((side, Piece::Rook));
+        }
+    }
+
+    /// the square a pawn can be taken on en passant
+    pub fn en_passant(&self) -> Option<BoardPos> {
+        self.en_passant
+    }
+
+    /// sets the en passant square, returns the previous one
+    pub fn set_en_passant(&mut self, pos: Option<BoardPos>) -> Option<BoardPos> {
+        std::mem::replace(&mut self.en_passant, pos)
+    }
+
+    /// plies since the last capture or pawn move
+    pub fn halfmove(&self) -> u32 {
+        self.halfmove
+    }
+
+    /// the number of the full move being played
+    pub fn fullmove(&self) -> u32 {
+        self.fullmove
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Side, Piece, BoardPos)> + '_ {
@@ -112,6 +352,252 @@ impl Board {
     pub fn remove_piece(&mut self, pos: &BoardPos) -> Option<(Side, Piece)> {
         self.pieces.remove(pos)
     }
+
+    /// all pseudo legal moves for `side` in a stable square order,
+    /// castling never passes through check
+    pub fn moves(&self, side: Side) -> impl Iterator<Item = Move> + '_ {
+        let last_rank = match side {
+            Side::White => 8,
+            Side::Black => 1,
+        };
+        BoardPos::iter()
+            .filter_map(move |from| match self.get_piece(&from) {
+                Some((s, piece)) if s == side => Some((from, piece)),
+                _ => None,
+            })
+            .flat_map(move |(from, piece)| {
+                let en_passant = self
+                    .en_passant
+                    .filter(|to| piece == Piece::Pawn && self.pawn_attacks(from, side, *to));
+                let castles = (piece == Piece::King)
+                    .then(|| self.castles(side))
+                    .into_iter()
+                    .flatten();
+                piece
+                    .moves(self, from, side)
+                    .chain(en_passant)
+                    .flat_map(move |to| {
+                        let promotions: &[Option<Piece>] =
+                            if piece == Piece::Pawn && to.rank == last_rank {
+                                &PROMOTIONS
+                            } else {
+                                &[None]
+                            };
+                        promotions.iter().map(move |&promotion| Move {
+                            from,
+                            to,
+                            promotion,
+                        })
+                    })
+                    .chain(castles)
+            })
+    }
+
+    /// castling moves with the squares between king and rook empty and the
+    /// king not passing through an attacked square
+    fn castles(&self, side: Side) -> impl Iterator<Item = Move> + '_ {
+        let rights = match side {
+            Side::White => 0..2,
+            Side::Black => 2..4,
+        };
+        rights
+            .filter(move |&i| self.castling[i])
+            .filter(move |&i| {
+                let [king, to, rook, _] = CASTLES[i];
+                let (low, high) = (king.file.min(rook.file), king.file.max(rook.file));
+                let empty =
+                    (low + 1..high).all(|file| self.get_piece(&sq(file, king.rank)).is_none());
+                let (low, high) = (king.file.min(to.file), king.file.max(to.file));
+                empty
+                    && (low..=high)
+                        .filter(|&file| file != to.file)
+                        .all(|file| !self.attacked(sq(file, king.rank), side.other()))
+            })
+            .map(|i| Move {
+                from: CASTLES[i][0],
+                to: CASTLES[i][1],
+                promotion: None,
+            })
+    }
+
+    /// if a pawn of `side` on `from` would attack `pos`
+    fn pawn_attacks(&self, from: BoardPos, side: Side, pos: BoardPos) -> bool {
+        let forward = match side {
+            Side::White => 1,
+            Side::Black => -1,
+        };
+        pos.rank as i32 - from.rank as i32 == forward
+            && (pos.file as i32 - from.file as i32).abs() == 1
+    }
+
+    /// pseudo legal moves that do not leave own king attacked
+    pub fn legal_moves(&self, side: Side) -> Vec<Move> {
+        let mut board = self.clone();
+        self.moves(side)
+            .filter(|&mv| {
+                let captured = board.play(mv);
+                let legal = !board.in_check(side);
+                board.unplay(mv, captured);
+                legal
+            })
+            .collect()
+    }
+
+    /// moves the piece and returns whatever was captured, a king moving two
+    /// files castles and a pawn moving to the en passant square takes the
+    /// pawn beside it
+    pub fn play(&mut self, mv: Move) -> Option<(Side, Piece)> {
+        let moved = self.pieces.remove(&mv.from);
+        self.undo.push(Undo {
+            moved,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove: self.halfmove,
+            fullmove: self.fullmove,
+        });
+        let (side, piece) = moved?;
+
+        let mut captured = self.pieces.remove(&mv.to);
+        if let Some(pos) = self.en_passant_victim(mv, piece) {
+            captured = self.pieces.remove(&pos);
+        }
+        if let Some([_, _, rook, rook_to]) = Self::castle(mv, piece) {
+            if let Some(rook) = self.pieces.remove(&rook) {
+                self.pieces.insert(rook_to, rook);
+            }
+        }
+
+        self.en_passant = (piece == Piece::Pawn && mv.from.rank.abs_diff(mv.to.rank) == 2)
+            .then(|| sq(mv.from.file, (mv.from.rank + mv.to.rank) / 2));
+        self.halfmove = if piece == Piece::Pawn || captured.is_some() {
+            0
+        } else {
+            self.halfmove + 1
+        };
+        if side == Side::Black {
+            self.fullmove += 1;
+        }
+        for (i, [king, _, rook, _]) in CASTLES.into_iter().enumerate() {
+            if [king, rook]
+                .iter()
+                .any(|&pos| pos == mv.from || pos == mv.to)
+            {
+                self.castling[i] = false;
+            }
+        }
+
+        let piece = match mv.promotion {
+            Some(promotion) if piece == Piece::Pawn => promotion,
+            _ => piece,
+        };
+        self.pieces.insert(mv.to, (side, piece));
+        captured
+    }
+
+    /// reverts the last [`Board::play`]
+    pub fn unplay(&mut self, mv: Move, captured: Option<(Side, Piece)>) {
+        let Some(undo) = self.undo.pop() else {
+            return;
+        };
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove = undo.halfmove;
+        self.fullmove = undo.fullmove;
+
+        let Some((side, piece)) = undo.moved else {
+            return;
+        };
+        self.pieces.remove(&mv.to);
+        self.pieces.insert(mv.from, (side, piece));
+        if let Some([_, _, rook, rook_to]) = Self::castle(mv, piece) {
+            if let Some(rook_piece) = self.pieces.remove(&rook_to) {
+                self.pieces.insert(rook, rook_piece);
+            }
+        }
+        if let Some(captured) = captured {
+            let pos = self.en_passant_victim(mv, piece).unwrap_or(mv.to);
+            self.pieces.insert(pos, captured);
+        }
+    }
+
+    /// the square of the pawn taken if `mv` is an en passant capture
+    fn en_passant_victim(&self, mv: Move, piece: Piece) -> Option<BoardPos> {
+        (piece == Piece::Pawn && Some(mv.to) == self.en_passant && mv.from.file != mv.to.file)
+            .then(|| sq(mv.to.file, mv.from.rank))
+    }
+
+    /// king and rook squares if `mv` castles
+    fn castle(mv: Move, piece: Piece) -> Option<[BoardPos; 4]> {
+        if piece != Piece::King {
+            return None;
+        }
+        CASTLES
+            .into_iter()
+            .find(|&[king, to, _, _]| mv.from == king && mv.to == to)
+    }
+
+    pub fn king(&self, side: Side) -> Option<BoardPos> {
+        self.iter()
+            .find(|&(s, piece, _)| s == side && piece == Piece::King)
+            .map(|(_, _, pos)| pos)
+    }
+
+    pub fn attacked(&self, pos: BoardPos, by: Side) -> bool {
+        self.iter()
+            .filter(|&(side, _, _)| side == by)
+            .any(|(side, piece, from)| match piece {
+                // pawns only attack diagonally, even empty squares
+                Piece::Pawn => self.pawn_attacks(from, side, pos),
+                _ => piece.moves(self, from, side).any(|to| to == pos),
+            })
+    }
+
+    /// zobrist hash of the position with `side` to move
+    pub fn hash(&self, side: Side) -> u64 {
+        let mut init = match side {
+            Side::White => 0,
+            Side::Black => ZOBRIST[2 * 6 * 64],
+        };
+        for (i, &right) in self.castling.iter().enumerate() {
+            if right {
+                init ^= ZOBRIST[ZOBRIST_CASTLING + i];
+            }
+        }
+        if let Some(pos) = self.en_passant {
+            init ^= ZOBRIST[ZOBRIST_EN_PASSANT + pos.file as usize - 1];
+        }
+        self.iter().fold(init, |hash, (side, piece, pos)| {
+            hash ^ ZOBRIST[(side as usize * 6 + piece as usize) * 64 + pos.to_usize()]
+        })
+    }
+
+    pub fn in_check(&self, side: Side) -> bool {
+        self.king(side)
+            .is_some_and(|pos| self.attacked(pos, side.other()))
+    }
+
+    /// checks that a game can start from this position with `side` to move
+    pub fn validate(&self, side: Side) -> Result<(), PositionError> {
+        for king_side in [Side::White, Side::Black] {
+            let kings = self
+                .iter()
+                .filter(|&(s, piece, _)| s == king_side && piece == Piece::King)
+                .count();
+            if kings != 1 {
+                return Err(PositionError::KingCount(king_side, kings));
+            }
+        }
+        if let Some((_, _, pos)) = self
+            .iter()
+            .find(|&(_, piece, pos)| piece == Piece::Pawn && (pos.rank == 1 || pos.rank == 8))
+        {
+            return Err(PositionError::PawnOnBackRank(pos));
+        }
+        if self.in_check(side.other()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+        Ok(())
+    }
 }
 
 impl BoardPos {
@@ -135,8 +621,24 @@ impl BoardPos {
     pub const fn to_usize(self) -> usize {
         self.file as usize + self.rank as usize * 8 - 9
     }
+
+    pub const fn from_usize(i: usize) -> Option<Self> {
+        Self::new((i % 8) as i32 + 1, (i / 8) as i32 + 1)
+    }
+}
+
+/// the same pieces, castling rights and en passant square, the move
+/// counters do not matter
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.pieces == other.pieces
+            && self.castling == other.castling
+            && self.en_passant == other.en_passant
+    }
 }
 
+impl Eq for Board {}
+
 impl Iterator for Board {
     type Item = (Side, Piece);
 
@@ -156,3 +658,237 @@ impl fmt::Display for BoardPos {
         write!(f, "{}{}", (b'a' + self.file - 1) as char, self.rank)
     }
 }
+
+impl BoardPos {
+    /// parses `e4` style square names
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let file = chars.next()?;
+        let rank = chars.next()?;
+        if chars.next().is_some() || !file.is_ascii_lowercase() || !rank.is_ascii_digit() {
+            return None;
+        }
+        Self::new((file as u8 - b'a') as i32 + 1, (rank as u8 - b'0') as i32)
+    }
+}
+
+impl Move {
+    /// parses long algebraic `e2e4` or `e7e8q` style moves
+    pub fn parse(s: &str) -> Option<Self> {
+        if !s.is_ascii() || !(4..=5).contains(&s.len()) {
+            return None;
+        }
+        let promotion = match s[4..].chars().next() {
+            None => None,
+            Some(c @ ('n' | 'b' | 'r' | 'q')) => Some(Piece::from_char(c)?.1),
+            Some(_) => return None,
+        };
+        Some(Self {
+            from: BoardPos::parse(&s[..2])?,
+            to: BoardPos::parse(&s[2..4])?,
+            promotion,
+        })
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(piece) = self.promotion {
+            write!(f, "{}", piece.to_char(Side::Black))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "empty fen"),
+            FenError::Piece(c) => write!(f, "invalid piece '{c}'"),
+            FenError::RankLength(rank) => write!(f, "rank {rank} does not have 8 files"),
+            FenError::RankCount => write!(f, "fen does not have 8 ranks"),
+            FenError::Side(side) => write!(f, "invalid side to move '{side}'"),
+            FenError::Castling(castling) => write!(f, "invalid castling rights '{castling}'"),
+            FenError::EnPassant(pos) => write!(f, "invalid en passant square '{pos}'"),
+            FenError::Clock(clock) => write!(f, "invalid halfmove clock '{clock}'"),
+            FenError::Fullmove(number) => write!(f, "invalid fullmove number '{number}'"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::KingCount(side, count) => {
+                let side = match side {
+                    Side::White => "white",
+                    Side::Black => "black",
+                };
+                write!(f, "{side} has {count} kings")
+            }
+            PositionError::PawnOnBackRank(pos) => write!(f, "pawn on {pos}"),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(fen: &str) -> Result<(), PositionError> {
+        let (board, side) = Board::from_fen(fen).unwrap();
+        board.validate(side)
+    }
+
+    #[test]
+    fn fen_roundtrip() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b - - 3 7";
+        let (mut board, side) = Board::from_fen(fen).unwrap();
+        assert_eq!(side, Side::Black);
+        assert_eq!(board.to_fen(side), fen);
+        // black moves end a full move
+        board.play(Move::parse("g8f6").unwrap());
+        assert_eq!(board.fullmove(), 8);
+        board.play(Move::parse("b1c3").unwrap());
+        assert_eq!(board.fullmove(), 8);
+        assert_eq!(Board::starting().to_fen(Side::White).split(' ').count(), 6);
+
+        assert_eq!(Board::from_fen(""), Err(FenError::Empty));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8 w"), Err(FenError::RankCount));
+        assert_eq!(
+            Board::from_fen("9/8/8/8/8/8/8/8 w"),
+            Err(FenError::Piece('9'))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 x"),
+            Err(FenError::Side("x".to_string()))
+        );
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 x"),
+            Err(FenError::Fullmove("x".to_string()))
+        );
+    }
+
+    fn perft(board: &mut Board, side: Side, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let before = board.clone();
+        let mut nodes = 0;
+        for mv in board.legal_moves(side) {
+            let captured = board.play(mv);
+            nodes += perft(board, side.other(), depth - 1);
+            board.unplay(mv, captured);
+            assert_eq!(*board, before, "{mv}");
+        }
+        nodes
+    }
+
+    #[test]
+    fn perft_counts() {
+        // castling, en passant and promotions all show up in these
+        for (fen, depth, nodes) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                3,
+                8902,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                2,
+                2039,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                2,
+                264,
+            ),
+        ] {
+            let (mut board, side) = Board::from_fen(fen).unwrap();
+            assert_eq!(perft(&mut board, side, depth), nodes, "{fen}");
+        }
+    }
+
+    #[test]
+    fn castling_rights_and_en_passant() {
+        let (mut board, side) = Board::from_fen("r3k2r/8/8/8/1p6/8/P7/R3K2R w KQkq - 3 1").unwrap();
+        assert_eq!(board.castling(), [true; 4]);
+
+        board.play(Move::parse("a2a4").unwrap());
+        assert_eq!(
+            board.to_fen(side.other()),
+            "r3k2r/8/8/8/Pp6/8/8/R3K2R b KQkq a3 0 1"
+        );
+        let mv = Move::parse("b4a3").unwrap();
+        assert!(board.legal_moves(side.other()).contains(&mv));
+        let captured = board.play(mv);
+        assert_eq!(captured, Some((Side::White, Piece::Pawn)));
+        assert_eq!(board.to_fen(side), "r3k2r/8/8/8/8/p7/8/R3K2R w KQkq - 0 2");
+        board.unplay(mv, captured);
+        assert_eq!(
+            board.to_fen(side.other()),
+            "r3k2r/8/8/8/Pp6/8/8/R3K2R b KQkq a3 0 1"
+        );
+
+        // taking a rook takes its right away
+        board.play(Move::parse("h8h1").unwrap());
+        assert_eq!(board.castling(), [false, true, false, true]);
+
+        // rights without the pieces at home are dropped
+        let (board, _) = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.castling(), [true, false, false, false]);
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K2R w X - 0 1"),
+            Err(FenError::Castling("X".to_string()))
+        );
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - e4 0 1"),
+            Err(FenError::EnPassant("e4".to_string()))
+        );
+    }
+
+    #[test]
+    fn parses_promotions() {
+        let mv = Move::parse("e7e8q").unwrap();
+        assert_eq!(mv.promotion, Some(Piece::Queen));
+        assert_eq!(mv.to_string(), "e7e8q");
+        assert_eq!(Move::parse("e7e8k"), None);
+        assert_eq!(Move::parse("e7e8Q"), None);
+    }
+
+    #[test]
+    fn validates_positions() {
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4K3 w"), Ok(()));
+        assert_eq!(Board::starting().validate(Side::White), Ok(()));
+
+        assert_eq!(
+            validate("8/8/8/8/8/8/8/4K3 w"),
+            Err(PositionError::KingCount(Side::Black, 0))
+        );
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/2K1K3 w"),
+            Err(PositionError::KingCount(Side::White, 2))
+        );
+        assert_eq!(
+            validate("4k2P/8/8/8/8/8/8/4K3 w"),
+            Err(PositionError::PawnOnBackRank(
+                BoardPos::parse("h8").unwrap()
+            ))
+        );
+        // white to move could take the king
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/4R1K1 w"),
+            Err(PositionError::OpponentInCheck)
+        );
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4R1K1 b"), Ok(()));
+    }
+}
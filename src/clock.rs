@@ -0,0 +1,339 @@
+use crate::{
+    piece::{Piece, Side},
+    time::Limits,
+    Board,
+};
+use core::fmt;
+use std::time::{Duration, Instant};
+
+//
+
+/// what a side gets back for a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bonus {
+    /// sudden death
+    #[default]
+    None,
+    /// fischer, added after every move
+    Increment(Duration),
+    /// bronstein, the time used is given back up to this much
+    Delay(Duration),
+}
+
+/// `time` for the next `moves` moves, the last period without `moves`
+/// lasts for the rest of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+/// periods played one after the other, a last period with `moves` repeats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+}
+
+/// a chess clock for both sides
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    /// indexed by side, not counting the running side's current move
+    remaining: [Duration; 2],
+    /// period each side plays in
+    period: [usize; 2],
+    /// moves made in the current period
+    moves: [u32; 2],
+    running: Option<(Side, Instant)>,
+}
+
+//
+
+impl TimeControl {
+    /// periods separated by `,`, each `[moves/]minutes[+increment|d delay]`
+    /// with the bonus in seconds, like `40/90+30,30+30` or `5d2`
+    pub fn parse(s: &str) -> Option<Self> {
+        let periods = s
+            .split(',')
+            .map(Period::parse)
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { periods })
+    }
+
+    fn period(&self, i: usize) -> &Period {
+        // a last period with a move count repeats
+        &self.periods[i.min(self.periods.len() - 1)]
+    }
+}
+
+impl Period {
+    fn parse(s: &str) -> Option<Self> {
+        let (moves, rest) = match s.split_once('/') {
+            Some((moves, rest)) => (Some(moves.parse().ok().filter(|&n| n > 0)?), rest),
+            None => (None, s),
+        };
+        let (time, bonus) = if let Some((time, inc)) = rest.split_once('+') {
+            (time, Bonus::Increment(seconds(inc)?))
+        } else if let Some((time, delay)) = rest.split_once('d') {
+            (time, Bonus::Delay(seconds(delay)?))
+        } else {
+            (rest, Bonus::None)
+        };
+        let time = seconds(time)?.checked_mul(60)?;
+        (!time.is_zero()).then_some(Self { moves, time, bonus })
+    }
+}
+
+impl Clock {
+    /// both sides start with the first period, the clock is stopped
+    pub fn new(control: TimeControl) -> Self {
+        let time = control.periods[0].time;
+        Self {
+            control,
+            remaining: [time; 2],
+            period: [0; 2],
+            moves: [0; 2],
+            running: None,
+        }
+    }
+
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// starts the clock of `side`
+    pub fn start(&mut self, side: Side) {
+        self.stop();
+        self.running = Some((side, Instant::now()));
+    }
+
+    /// stops whichever clock runs without any bonus
+    pub fn stop(&mut self) {
+        if let Some((side, start)) = self.running.take() {
+            let remaining = &mut self.remaining[side as usize];
+            *remaining = remaining.saturating_sub(start.elapsed());
+        }
+    }
+
+    /// `side` completed a move, adds its bonus and starts the other clock
+    ///
+    /// does nothing to a side that already ran out of time
+    pub fn press(&mut self, side: Side) {
+        let used = match self.running {
+            Some((running, start)) if running == side => start.elapsed(),
+            _ => Duration::ZERO,
+        };
+        self.running = None;
+
+        let i = side as usize;
+        if self.remaining[i] > used {
+            let period = *self.control.period(self.period[i]);
+            self.remaining[i] -= used;
+            self.remaining[i] = self.remaining[i].saturating_add(match period.bonus {
+                Bonus::None => Duration::ZERO,
+                Bonus::Increment(increment) => increment,
+                Bonus::Delay(delay) => used.min(delay),
+            });
+
+            self.moves[i] += 1;
+            if period.moves == Some(self.moves[i]) {
+                self.moves[i] = 0;
+                self.period[i] += 1;
+                let time = self.control.period(self.period[i]).time;
+                self.remaining[i] = self.remaining[i].saturating_add(time);
+            }
+        } else {
+            self.remaining[i] = Duration::ZERO;
+        }
+
+        self.running = Some((side.other(), Instant::now()));
+    }
+
+    /// time left for `side` right now
+    pub fn remaining(&self, side: Side) -> Duration {
+        let remaining = self.remaining[side as usize];
+        match self.running {
+            Some((running, start)) if running == side => remaining.saturating_sub(start.elapsed()),
+            _ => remaining,
+        }
+    }
+
+    pub fn is_running(&self, side: Side) -> bool {
+        matches!(self.running, Some((running, _)) if running == side)
+    }
+
+    /// the side whose time ran out
+    pub fn flagged(&self) -> Option<Side> {
+        [Side::White, Side::Black]
+            .into_iter()
+            .find(|&side| self.remaining(side).is_zero())
+    }
+
+    /// search limits for `side` with its time and period
+    ///
+    /// engines know nothing of delays, the delay is given as an increment
+    /// since the time used is given back up to it
+    pub fn limits(&self, side: Side) -> Limits {
+        let i = side as usize;
+        let period = self.control.period(self.period[i]);
+        let increment = match period.bonus {
+            Bonus::None => Duration::ZERO,
+            Bonus::Increment(bonus) | Bonus::Delay(bonus) => bonus,
+        };
+        let moves_to_go = period.moves.map(|moves| moves - self.moves[i]);
+        Limits::clock(self.remaining(side), increment, moves_to_go)
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i != 0 {
+                f.write_str(",")?;
+            }
+            if let Some(moves) = period.moves {
+                write!(f, "{moves}/")?;
+            }
+            write!(f, "{}", period.time.as_secs_f64() / 60.0)?;
+            match period.bonus {
+                Bonus::None => {}
+                Bonus::Increment(increment) => write!(f, "+{}", increment.as_secs_f64())?,
+                Bonus::Delay(delay) => write!(f, "d{}", delay.as_secs_f64())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//
+
+/// `h:mm:ss`, `m:ss` and tenths under 10 seconds
+pub fn format_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs < 10 {
+        format!("0:{:02}.{}", secs, time.subsec_millis() / 100)
+    } else if secs < 3600 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// whether `side` has the material to ever checkmate, even with the
+/// opponent's help
+///
+/// a side that runs out of time draws instead of losing when the opponent
+/// cannot
+pub fn can_mate(board: &Board, side: Side) -> bool {
+    let mut minors = 0;
+    let mut opponent_pieces = 0;
+    for (piece_side, piece, _) in board.iter() {
+        match (piece_side == side, piece) {
+            (_, Piece::King) => {}
+            (true, Piece::Knight | Piece::Bishop) => minors += 1,
+            (true, _) => return true,
+            (false, _) => opponent_pieces += 1,
+        }
+    }
+    // a lone minor piece only mates a king that blocks its own escape
+    minors >= 2 || (minors == 1 && opponent_pieces > 0)
+}
+
+fn seconds(s: &str) -> Option<Duration> {
+    let secs = s.parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_time_controls() {
+        let control = TimeControl::parse("40/90+30,30+30").unwrap();
+        assert_eq!(
+            control.periods,
+            [
+                Period {
+                    moves: Some(40),
+                    time: Duration::from_secs(90 * 60),
+                    bonus: Bonus::Increment(Duration::from_secs(30)),
+                },
+                Period {
+                    moves: None,
+                    time: Duration::from_secs(30 * 60),
+                    bonus: Bonus::Increment(Duration::from_secs(30)),
+                },
+            ]
+        );
+        assert_eq!(control.to_string(), "40/90+30,30+30");
+        assert_eq!(TimeControl::parse("5d2").unwrap().to_string(), "5d2");
+
+        for invalid in [
+            "", "0", "0/5", "x+2", "5+", "5,,3", "-1", "NaN", "1e20", "1e18", "5+1e20", "5d1e20",
+        ] {
+            assert_eq!(TimeControl::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn bonuses_and_periods() {
+        // presses without a running clock use no time
+        let mut clock = Clock::new(TimeControl::parse("2/1+5,1d3").unwrap());
+        clock.press(Side::White);
+        assert_eq!(clock.remaining(Side::White), Duration::from_secs(65));
+        assert!(clock.is_running(Side::Black));
+        assert_eq!(
+            clock.limits(Side::White).moves_to_go,
+            Some(1),
+            "one move left in the period"
+        );
+
+        // the second move ends the first period
+        clock.press(Side::White);
+        assert_eq!(clock.remaining(Side::White), Duration::from_secs(130));
+        assert_eq!(clock.limits(Side::White).moves_to_go, None);
+
+        // a delay gives back at most the time used
+        clock.press(Side::White);
+        assert_eq!(clock.remaining(Side::White), Duration::from_secs(130));
+        assert_eq!(clock.flagged(), None);
+    }
+
+    #[test]
+    fn huge_bonuses_saturate() {
+        let period = Period {
+            moves: Some(1),
+            time: Duration::MAX,
+            bonus: Bonus::Increment(Duration::MAX),
+        };
+        let mut clock = Clock::new(TimeControl {
+            periods: vec![period],
+        });
+        clock.press(Side::White);
+        clock.press(Side::White);
+        assert_eq!(clock.remaining(Side::White), Duration::MAX);
+    }
+
+    #[test]
+    fn mating_material() {
+        let can = |fen: &str| can_mate(&Board::parse_fen(fen).unwrap(), Side::White);
+        assert!(can("4k3/8/8/8/8/8/8/R3K3"));
+        assert!(can("4k3/8/8/8/8/8/8/1NB1K3"));
+        assert!(!can("4k3/8/8/8/8/8/8/1N2K3"));
+        // a pawn can promote
+        assert!(can("4k3/8/8/8/8/8/4P3/4K3"));
+        // the defender's own pieces can box its king in
+        assert!(can("4k3/3p4/8/8/8/8/8/1N2K3"));
+    }
+
+    #[test]
+    fn formats_times() {
+        assert_eq!(format_time(Duration::from_millis(9_450)), "0:09.4");
+        assert_eq!(format_time(Duration::from_secs(75)), "1:15");
+        assert_eq!(format_time(Duration::from_secs(3_725)), "1:02:05");
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{board::Move, mate::MateTree, piece::Side, Board};
+use std::{
+    fs,
+    io::{self, BufRead},
+    time::Instant,
+};
+
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfpnOptions {
+    /// proof table size in MiB, old entries are overwritten when it is full
+    pub hash: usize,
+    /// gives up after this many nodes
+    pub max_nodes: u64,
+    /// lines longer than this many plies count as disproved
+    pub max_ply: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proof {
+    /// the side to move mates, with every defence answered
+    Mate(MateTree),
+    /// no mate within `max_ply`
+    NoMate,
+    /// ran out of nodes
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DfpnResult {
+    pub proof: Proof,
+    pub nodes: u64,
+}
+
+/// depth first proof number search
+///
+/// the attacker (the side to move at the root) wants to mate, a node is
+/// proved once its proof number hits 0 and disproved once its disproof
+/// number does, every node only keeps searching while both stay below the
+/// thresholds given by its parent
+struct Dfpn {
+    board: Board,
+    attacker: Side,
+    options: DfpnOptions,
+    table: Vec<Entry>,
+    nodes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Entry {
+    key: u64,
+    pn: u32,
+    dn: u32,
+}
+
+//
+
+const INF: u32 = u32::MAX / 2;
+
+//
+
+/// tries to prove a mate for the side to move
+pub fn dfpn(board: &Board, side: Side, options: DfpnOptions) -> DfpnResult {
+    let len = (options.hash.max(1) * 1024 * 1024 / std::mem::size_of::<Entry>()).max(1);
+    let mut search = Dfpn {
+        board: board.clone(),
+        attacker: side,
+        options,
+        table: vec![Entry::default(); len],
+        nodes: 0,
+    };
+
+    let (pn, dn) = search.mid(side, 0, INF, INF);
+    let proof = if pn == 0 {
+        match search.proof(0) {
+            Some(tree) => Proof::Mate(tree),
+            None => Proof::Unknown,
+        }
+    } else if dn == 0 {
+        Proof::NoMate
+    } else {
+        Proof::Unknown
+    };
+
+    DfpnResult {
+        proof,
+        nodes: search.nodes,
+    }
+}
+
+/// `chess dfpn [max-plies] [hash=MiB] [nodes=N] [file]`, reads one fen per
+/// line from the file or stdin and prints the proofs
+pub fn run(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let mut options = DfpnOptions::default();
+    let mut file = None;
+    for arg in args {
+        let ok = match arg.split_once('=') {
+            Some(("hash", value)) => value.parse().map(|hash| options.hash = hash).is_ok(),
+            Some(("nodes", value)) => value.parse().map(|n| options.max_nodes = n).is_ok(),
+            _ => {
+                match arg.parse() {
+                    Ok(max_ply) => options.max_ply = max_ply,
+                    Err(_) => file = Some(arg),
+                }
+                continue;
+            }
+        };
+        if !ok {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid argument '{arg}'"),
+            ));
+        }
+    }
+
+    let input = match file {
+        Some(file) => fs::read_to_string(file)?,
+        None => io::stdin()
+            .lock()
+            .lines()
+            .collect::<io::Result<Vec<_>>>()?
+            .join("\n"),
+    };
+
+    for fen in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (board, side) = match Board::from_fen(fen) {
+            Ok(ok) => ok,
+            Err(err) => {
+                println!("{fen}: {err}");
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        let result = dfpn(&board, side, options);
+        let stats = format!("{} nodes in {:.1?}", result.nodes, start.elapsed());
+        match result.proof {
+            Proof::Mate(tree) => {
+                println!("{fen}: mate in {} ({stats})", tree.depth());
+                print!("{}", tree.format(&board, side));
+            }
+            Proof::NoMate => println!("{fen}: no mate in {} plies ({stats})", options.max_ply),
+            Proof::Unknown => println!("{fen}: unknown ({stats})"),
+        }
+        println!();
+    }
+    Ok(())
+}
+
+impl Default for DfpnOptions {
+    fn default() -> Self {
+        Self {
+            hash: 64,
+            max_nodes: 10_000_000,
+            max_ply: 64,
+        }
+    }
+}
+
+impl Dfpn {
+    /// the same position at another ply is another node, so a disproof
+    /// that only ran out of plies is never reused closer to the root
+    fn key(&self, side: Side, ply: u32) -> u64 {
+        self.board.hash(side) ^ (ply as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+
+    fn lookup(&self, key: u64) -> (u32, u32) {
+        let entry = self.table[(key % self.table.len() as u64) as usize];
+        if entry.key == key {
+            (entry.pn, entry.dn)
+        } else {
+            (1, 1)
+        }
+    }
+
+    fn store(&mut self, key: u64, pn: u32, dn: u32) {
+        let len = self.table.len() as u64;
+        self.table[(key % len) as usize] = Entry { key, pn, dn };
+    }
+
+    /// expands the node until its proof number reaches `thpn` or its
+    /// disproof number reaches `thdn`
+    fn mid(&mut self, side: Side, ply: u32, thpn: u32, thdn: u32) -> (u32, u32) {
+        self.nodes += 1;
+        let key = self.key(side, ply);
+        let attacking = side == self.attacker;
+
+        let moves = self.board.legal_moves(side);
+        if moves.is_empty() || ply >= self.options.max_ply {
+            let mated = moves.is_empty() && !attacking && self.board.in_check(side);
+            let (pn, dn) = if mated { (0, INF) } else { (INF, 0) };
+            self.store(key, pn, dn);
+            return (pn, dn);
+        }
+
+        let children = moves
+            .iter()
+            .map(|&mv| {
+                let captured = self.board.play(mv);
+                let key = self.key(side.other(), ply + 1);
+                self.board.unplay(mv, captured);
+                key
+            })
+            .collect::<Vec<_>>();
+
+        loop {
+            // (pn, dn) of the node and its most promising child
+            let mut pn = if attacking { INF } else { 0 };
+            let mut dn = if attacking { 0 } else { INF };
+            let mut best = 0;
+            let mut best_value = INF;
+            let mut second_value = INF;
+            for (i, &child) in children.iter().enumerate() {
+                let (child_pn, child_dn) = self.lookup(child);
+                let value = if attacking { child_pn } else { child_dn };
+                if attacking {
+                    pn = pn.min(child_pn);
+                    dn = (dn + child_dn).min(INF);
+                } else {
+                    pn = (pn + child_pn).min(INF);
+                    dn = dn.min(child_dn);
+                }
+                if value < best_value {
+                    second_value = best_value;
+                    best_value = value;
+                    best = i;
+                } else if value < second_value {
+                    second_value = value;
+                }
+            }
+            self.store(key, pn, dn);
+
+            if pn >= thpn || dn >= thdn || self.nodes >= self.options.max_nodes {
+                return (pn, dn);
+            }
+
+            let (child_pn, child_dn) = self.lookup(children[best]);
+            let (child_thpn, child_thdn) = if attacking {
+                (
+                    thpn.min(second_value.saturating_add(1)),
+                    (thdn - dn).saturating_add(child_dn).min(INF),
+                )
+            } else {
+                (
+                    (thpn - pn).saturating_add(child_pn).min(INF),
+                    thdn.min(second_value.saturating_add(1)),
+                )
+            };
+
+            let mv = moves[best];
+            let captured = self.board.play(mv);
+            self.mid(side.other(), ply + 1, child_thpn, child_thdn);
+            self.board.unplay(mv, captured);
+        }
+    }
+
+    /// whether the node is proved, searched again if the table forgot it
+    fn proved(&mut self, side: Side, ply: u32) -> bool {
+        let (pn, _) = self.lookup(self.key(side, ply));
+        pn == 0 || self.mid(side, ply, INF, INF).0 == 0
+    }
+
+    /// the proof tree below a proved attacker node
+    fn proof(&mut self, ply: u32) -> Option<MateTree> {
+        let side = self.attacker;
+        for retry in [false, true] {
+            if retry {
+                // the proved child got overwritten, search it again
+                self.mid(side, ply, INF, INF);
+            }
+
+            for mv in self.board.legal_moves(side) {
+                let captured = self.board.play(mv);
+                let (pn, _) = self.lookup(self.key(side.other(), ply + 1));
+                let replies = if pn == 0 {
+                    self.defences(ply + 1)
+                } else {
+                    None
+                };
+                self.board.unplay(mv, captured);
+
+                if let Some(replies) = replies {
+                    return Some(MateTree { mv, replies });
+                }
+            }
+        }
+        None
+    }
+
+    /// every defence of a proved defender node with its proof
+    fn defences(&mut self, ply: u32) -> Option<Vec<(Move, MateTree)>> {
+        let side = self.attacker.other();
+        let mut replies = Vec::new();
+        for mv in self.board.legal_moves(side) {
+            let captured = self.board.play(mv);
+            let tree = if self.proved(side.other(), ply + 1) {
+                self.proof(ply + 1)
+            } else {
+                None
+            };
+            self.board.unplay(mv, captured);
+
+            replies.push((mv, tree?));
+        }
+        Some(replies)
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mate::tests::verify;
+
+    fn prove(fen: &str, options: DfpnOptions) -> Proof {
+        let (mut board, side) = Board::from_fen(fen).unwrap();
+        let result = dfpn(&board, side, options);
+        if let Proof::Mate(tree) = &result.proof {
+            verify(&mut board, side, tree);
+        }
+        result.proof
+    }
+
+    fn small() -> DfpnOptions {
+        DfpnOptions {
+            hash: 1,
+            max_nodes: 100_000,
+            max_ply: 8,
+        }
+    }
+
+    #[test]
+    fn proves_mates() {
+        let Proof::Mate(tree) = prove("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", small()) else {
+            panic!("back rank mate not proved");
+        };
+        assert_eq!(tree.depth(), 1);
+
+        // the key is quiet, the defender gets a reply
+        let Proof::Mate(tree) = prove("k7/8/2K5/8/8/8/8/7R w - - 0 1", small()) else {
+            panic!("mate in 2 not proved");
+        };
+        assert!(!tree.replies.is_empty());
+    }
+
+    #[test]
+    fn disproves_and_gives_up() {
+        let options = DfpnOptions {
+            max_ply: 4,
+            ..small()
+        };
+        assert_eq!(
+            prove("k7/8/1K6/8/8/8/8/8 w - - 0 1", options),
+            Proof::NoMate
+        );
+
+        let options = DfpnOptions {
+            max_nodes: 1,
+            ..small()
+        };
+        assert_eq!(
+            prove("k7/8/2K5/8/8/8/8/7R w - - 0 1", options),
+            Proof::Unknown
+        );
+    }
+}
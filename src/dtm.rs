@@ -0,0 +1,824 @@
+use crate::{
+    piece::{Piece, Side},
+    Board, BoardPos,
+};
+use core::fmt;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::Instant,
+};
+
+//
+
+/// endings the retrograde generator knows, the strong side has the king and
+/// the listed pieces against a lone king
+///
+/// a pawn wins by promoting, those positions are looked up in the queen and
+/// rook tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Material {
+    Kqk,
+    Krk,
+    Kpk,
+    Kbnk,
+}
+
+/// distance to mate table of one ending, built with retrograde analysis
+///
+/// one byte per position, 0 for draws and illegal positions, otherwise one
+/// more than the plies to mate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DtmTable {
+    material: Material,
+    dtm: Vec<u8>,
+}
+
+/// result of a table probe from the point of view of the side to move,
+/// distances in plies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dtm {
+    /// mates in this many plies
+    Mate(u32),
+    /// gets mated in this many plies, 0 is mated right now
+    Mated(u32),
+    Draw,
+}
+
+/// an unpacked table index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    /// the strong side is to move
+    strong: bool,
+    wk: u8,
+    bk: u8,
+    pieces: [u8; 2],
+}
+
+//
+
+const MAGIC: &[u8; 4] = b"DTM1";
+
+/// starts a run, values never get this high
+const RUN: u8 = 0x80;
+
+/// shorter runs are cheaper as plain values
+const MIN_RUN: usize = 4;
+
+const KING_STEPS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [
+    (-1, 2),
+    (1, 2),
+    (-1, -2),
+    (1, -2),
+    (-2, -1),
+    (-2, 1),
+    (2, -1),
+    (2, 1),
+];
+
+const LEVEL: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+const DIAGONAL: [(i32, i32); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+//
+
+/// `chess dtm <dir> [KQK KRK KPK KBNK]`
+pub fn run(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let dir = args.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: chess dtm <dir> [KQK KRK KPK KBNK]",
+        )
+    })?;
+    let mut materials = args
+        .map(|name| {
+            Material::parse(&name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unknown ending '{name}'"),
+                )
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    if materials.is_empty() {
+        materials = Material::ALL.to_vec();
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    for material in materials {
+        let start = Instant::now();
+        let table = DtmTable::generate(material);
+        let path = Path::new(&dir).join(material.file_name());
+        table.save(&path)?;
+        println!(
+            "{material}: longest mate in {} moves, generated in {:.1?}, written to {}",
+            table.longest_mate(),
+            start.elapsed(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+impl Material {
+    pub const ALL: [Material; 4] = [Material::Kqk, Material::Krk, Material::Kpk, Material::Kbnk];
+
+    /// pieces of the strong side besides the king, in [`Piece`] order
+    pub const fn pieces(self) -> &'static [Piece] {
+        match self {
+            Material::Kqk => &[Piece::Queen],
+            Material::Krk => &[Piece::Rook],
+            Material::Kpk => &[Piece::Pawn],
+            Material::Kbnk => &[Piece::Knight, Piece::Bishop],
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Material::Kqk => "KQK",
+            Material::Krk => "KRK",
+            Material::Kpk => "KPK",
+            Material::Kbnk => "KBNK",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|material| material.name().eq_ignore_ascii_case(s))
+    }
+
+    pub fn file_name(self) -> String {
+        format!("{}.dtm", self.name())
+    }
+
+    /// the ending on the board and its strong side
+    pub fn of(board: &Board) -> Option<(Self, Side)> {
+        let mut white = Vec::new();
+        let mut black = Vec::new();
+        for (side, piece, _) in board.iter() {
+            match side {
+                Side::White => white.push(piece),
+                Side::Black => black.push(piece),
+            }
+        }
+
+        let (strong, mut pieces) = match (white.len(), black.len()) {
+            (_, 1) => (Side::White, white),
+            (1, _) => (Side::Black, black),
+            _ => return None,
+        };
+        pieces.retain(|&piece| piece != Piece::King);
+        pieces.sort_by_key(|&piece| piece as u8);
+        Self::ALL
+            .into_iter()
+            .find(|material| material.pieces() == pieces)
+            .map(|material| (material, strong))
+    }
+
+    fn len(self) -> usize {
+        2 * 64 * 64 * 64usize.pow(self.pieces().len() as u32)
+    }
+}
+
+impl DtmTable {
+    /// solves the ending with retrograde analysis
+    ///
+    /// every position where the weak side is mated is found first, then
+    /// positions are walked backwards one ply at a time: the strong side
+    /// wins if one move reaches a lost position or promotes into one, the
+    /// weak side loses once every one of its moves reaches a won position
+    pub fn generate(material: Material) -> Self {
+        let len = material.len();
+        let mut dtm = vec![0u8; len];
+        // weak side moves not yet known to lose
+        let mut escapes = vec![0u8; len];
+
+        let mut lost = Vec::new();
+        for index in 0..len {
+            let pos = Position::unpack(material, index);
+            if pos.strong || !pos.legal(material) {
+                continue;
+            }
+            let moves = pos.weak_moves(material);
+            if moves == 0 && pos.weak_in_check(material) {
+                dtm[index] = 1;
+                lost.push(index);
+            }
+            escapes[index] = moves;
+        }
+
+        let mut promotions = Self::promotions(material).into_iter().peekable();
+        let mut ply = 0u8;
+        while !lost.is_empty() || promotions.peek().is_some() {
+            // strong side promotes into a lost position
+            let mut won = Vec::new();
+            while let Some((value, index)) = promotions.next_if(|&(value, _)| value <= ply + 2) {
+                if dtm[index] == 0 {
+                    dtm[index] = value;
+                    won.push(index);
+                }
+            }
+
+            // strong side moves into a lost position
+            for &index in lost.iter() {
+                let pos = Position::unpack(material, index);
+                pos.strong_unmoves(material, |prev| {
+                    let prev_index = prev.pack(material);
+                    if dtm[prev_index] == 0 {
+                        dtm[prev_index] = ply + 2;
+                        won.push(prev_index);
+                    }
+                });
+            }
+
+            // weak side positions with every move losing
+            lost.clear();
+            for &index in won.iter() {
+                let pos = Position::unpack(material, index);
+                pos.weak_unmoves(material, |prev| {
+                    let prev_index = prev.pack(material);
+                    if dtm[prev_index] == 0 {
+                        escapes[prev_index] -= 1;
+                        if escapes[prev_index] == 0 {
+                            dtm[prev_index] = ply + 3;
+                            lost.push(prev_index);
+                        }
+                    }
+                });
+            }
+            ply += 2;
+        }
+
+        Self { material, dtm }
+    }
+
+    /// strong side to move positions where promoting the pawn wins, with
+    /// their values sorted, a rook is only better when a queen stalemates
+    fn promotions(material: Material) -> Vec<(u8, usize)> {
+        if !material.pieces().contains(&Piece::Pawn) {
+            return Vec::new();
+        }
+        let tables = [Material::Kqk, Material::Krk].map(Self::generate);
+
+        let mut wins = Vec::new();
+        for index in 0..material.len() {
+            let pos = Position::unpack(material, index);
+            let pawn = pos.pieces[0];
+            if !pos.strong || !pos.legal(material) || pawn < 48 {
+                continue;
+            }
+            let to = pawn + 8;
+            if pos.occupied(material) & bit(to) != 0 {
+                continue;
+            }
+            let promoted = Position {
+                strong: false,
+                pieces: [to, 0],
+                ..pos
+            };
+            let best = tables
+                .iter()
+                .filter_map(|table| match table.dtm[promoted.pack(table.material)] {
+                    0 => None,
+                    dtm => Some(dtm + 1),
+                })
+                .min();
+            if let Some(value) = best {
+                wins.push((value, index));
+            }
+        }
+        wins.sort_unstable();
+        wins
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    /// the longest forced mate in full moves with the strong side to move
+    pub fn longest_mate(&self) -> u32 {
+        let half = self.dtm.len() / 2;
+        let dtm = self.dtm[..half].iter().max().copied().unwrap_or(0);
+        (dtm.saturating_sub(1) as u32).div_ceil(2)
+    }
+
+    /// `None` if the board is not this ending
+    pub fn probe(&self, board: &Board, side: Side) -> Option<Dtm> {
+        let (material, strong) = Material::of(board)?;
+        if material != self.material {
+            return None;
+        }
+
+        // the tables are built with white as the strong side
+        let square = |pos: BoardPos| match strong {
+            Side::White => pos.to_usize() as u8,
+            Side::Black => pos.file - 1 + (8 - pos.rank) * 8,
+        };
+        let mut pos = Position {
+            strong: side == strong,
+            wk: square(board.king(strong)?),
+            bk: square(board.king(strong.other())?),
+            pieces: [0; 2],
+        };
+        for (i, &piece) in material.pieces().iter().enumerate() {
+            let (_, _, at) = board.iter().find(|&(s, p, _)| s == strong && p == piece)?;
+            pos.pieces[i] = square(at);
+        }
+
+        let plies = match self.dtm[pos.pack(material)] {
+            0 => return Some(Dtm::Draw),
+            dtm => dtm as u32 - 1,
+        };
+        Some(if pos.strong {
+            Dtm::Mate(plies)
+        } else {
+            Dtm::Mated(plies)
+        })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+
+    /// `DTM1`, the ending name and then the values, runs of the same value
+    /// are written as `0x80`, the value and the length as a little endian
+    /// base 128 varint
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        let name = self.material.name();
+        out.write_all(&[name.len() as u8])?;
+        out.write_all(name.as_bytes())?;
+
+        let mut i = 0;
+        while i < self.dtm.len() {
+            let value = self.dtm[i];
+            let run = self.dtm[i..].iter().take_while(|&&v| v == value).count();
+            if run < MIN_RUN {
+                out.write_all(&self.dtm[i..i + run])?;
+            } else {
+                out.write_all(&[RUN, value])?;
+                let mut left = run;
+                loop {
+                    let byte = (left & 0x7f) as u8;
+                    left >>= 7;
+                    if left == 0 {
+                        out.write_all(&[byte])?;
+                        break;
+                    }
+                    out.write_all(&[byte | 0x80])?;
+                }
+            }
+            i += run;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| invalid("not a dtm table"))?;
+        let (&name_len, rest) = rest.split_first().ok_or_else(|| invalid("truncated"))?;
+        if rest.len() < name_len as usize {
+            return Err(invalid("truncated"));
+        }
+        let (name, mut rest) = rest.split_at(name_len as usize);
+        let material = std::str::from_utf8(name)
+            .ok()
+            .and_then(Material::parse)
+            .ok_or_else(|| invalid("unknown ending"))?;
+
+        let mut dtm = Vec::with_capacity(material.len());
+        while let Some((&byte, next)) = rest.split_first() {
+            rest = next;
+            if byte != RUN {
+                dtm.push(byte);
+                continue;
+            }
+
+            let (&value, next) = rest.split_first().ok_or_else(|| invalid("truncated"))?;
+            rest = next;
+            let mut run = 0usize;
+            let mut shift = 0;
+            loop {
+                let (&byte, next) = rest.split_first().ok_or_else(|| invalid("truncated"))?;
+                rest = next;
+                run |= ((byte & 0x7f) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            dtm.resize(dtm.len() + run, value);
+        }
+        if dtm.len() != material.len() {
+            return Err(invalid("wrong table size"));
+        }
+
+        Ok(Self { material, dtm })
+    }
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Dtm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dtm::Mate(plies) => write!(f, "mate in {}", plies.div_ceil(2)),
+            Dtm::Mated(plies) => write!(f, "mated in {}", plies.div_ceil(2)),
+            Dtm::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+// the generator works on square indices and bitboards with white as the
+// strong side, the piece rules follow `Piece::moves`
+
+impl Position {
+    fn unpack(material: Material, mut index: usize) -> Self {
+        let mut pieces = [0; 2];
+        for i in (0..material.pieces().len()).rev() {
+            pieces[i] = (index % 64) as u8;
+            index /= 64;
+        }
+        let bk = (index % 64) as u8;
+        index /= 64;
+        let wk = (index % 64) as u8;
+        index /= 64;
+        Self {
+            strong: index == 0,
+            wk,
+            bk,
+            pieces,
+        }
+    }
+
+    fn pack(&self, material: Material) -> usize {
+        let mut index = (!self.strong as usize * 64 + self.wk as usize) * 64 + self.bk as usize;
+        for &square in self.pieces[..material.pieces().len()].iter() {
+            index = index * 64 + square as usize;
+        }
+        index
+    }
+
+    /// strong side pieces with their squares, king last
+    fn strong_pieces(&self, material: Material) -> impl Iterator<Item = (Piece, u8)> + '_ {
+        material
+            .pieces()
+            .iter()
+            .copied()
+            .zip(self.pieces)
+            .chain([(Piece::King, self.wk)])
+    }
+
+    fn occupied(&self, material: Material) -> u64 {
+        self.strong_pieces(material)
+            .fold(bit(self.bk), |occ, (_, square)| occ | bit(square))
+    }
+
+    fn legal(&self, material: Material) -> bool {
+        let occ = self.occupied(material);
+        if occ.count_ones() as usize != material.pieces().len() + 2 {
+            return false;
+        }
+        if king_distance(self.wk, self.bk) <= 1 {
+            return false;
+        }
+        // pawns are never on the first or last rank
+        if self
+            .strong_pieces(material)
+            .any(|(piece, square)| piece == Piece::Pawn && !(8..56).contains(&square))
+        {
+            return false;
+        }
+        // the weak king cannot be in check with the strong side to move
+        !(self.strong && self.weak_in_check(material))
+    }
+
+    fn weak_in_check(&self, material: Material) -> bool {
+        let occ = self.occupied(material);
+        self.strong_pieces(material)
+            .any(|(piece, square)| attacks(piece, square, occ) & bit(self.bk) != 0)
+    }
+
+    /// number of legal weak king moves, captures included
+    fn weak_moves(&self, material: Material) -> u8 {
+        let mut moves = 0;
+        for to in steps(self.bk, &KING_STEPS) {
+            if to == self.wk {
+                continue;
+            }
+            let occ = self.occupied(material) & !bit(self.bk) | bit(to);
+            let attacked = self
+                .strong_pieces(material)
+                .filter(|&(_, square)| square != to)
+                .any(|(piece, square)| attacks(piece, square, occ) & bit(to) != 0);
+            if !attacked {
+                moves += 1;
+            }
+        }
+        moves
+    }
+
+    /// strong side to move positions that reach `self` in one move,
+    /// promotions are not unmoved
+    fn strong_unmoves(&self, material: Material, mut f: impl FnMut(Position)) {
+        let occ = self.occupied(material);
+        for (i, (piece, to)) in self.strong_pieces(material).enumerate() {
+            let froms = match piece {
+                // pawns step back one square, or two from the fourth rank
+                Piece::Pawn => {
+                    let mut froms = 0;
+                    if to >= 16 && occ & bit(to - 8) == 0 {
+                        froms |= bit(to - 8);
+                        if (24..32).contains(&to) && occ & bit(to - 16) == 0 {
+                            froms |= bit(to - 16);
+                        }
+                    }
+                    froms
+                }
+                // pieces move back the way they attack
+                _ => attacks(piece, to, occ) & !occ,
+            };
+            for from in (0..64).filter(|&from| froms & bit(from) != 0) {
+                let mut prev = Position {
+                    strong: true,
+                    ..*self
+                };
+                if piece == Piece::King {
+                    prev.wk = from;
+                } else {
+                    prev.pieces[i] = from;
+                }
+                if prev.legal(material) {
+                    f(prev);
+                }
+            }
+        }
+    }
+
+    /// weak side to move positions that reach `self` with a quiet move
+    fn weak_unmoves(&self, material: Material, mut f: impl FnMut(Position)) {
+        let occ = self.occupied(material);
+        for from in steps(self.bk, &KING_STEPS) {
+            if occ & bit(from) != 0 {
+                continue;
+            }
+            let prev = Position {
+                strong: false,
+                bk: from,
+                ..*self
+            };
+            if prev.legal(material) {
+                f(prev);
+            }
+        }
+    }
+}
+
+const fn bit(square: u8) -> u64 {
+    1 << square
+}
+
+fn king_distance(a: u8, b: u8) -> u8 {
+    let file = (a % 8).abs_diff(b % 8);
+    let rank = (a / 8).abs_diff(b / 8);
+    file.max(rank)
+}
+
+fn offset(square: u8, (dx, dy): (i32, i32)) -> Option<u8> {
+    let x = (square % 8) as i32 + dx;
+    let y = (square / 8) as i32 + dy;
+    ((0..8).contains(&x) && (0..8).contains(&y)).then_some((x + y * 8) as u8)
+}
+
+fn steps(square: u8, dirs: &[(i32, i32)]) -> impl Iterator<Item = u8> + '_ {
+    dirs.iter().filter_map(move |&dir| offset(square, dir))
+}
+
+fn rays(square: u8, dirs: &[(i32, i32)], occ: u64) -> u64 {
+    let mut attacks = 0;
+    for &dir in dirs {
+        let mut at = square;
+        while let Some(next) = offset(at, dir) {
+            attacks |= bit(next);
+            if occ & bit(next) != 0 {
+                break;
+            }
+            at = next;
+        }
+    }
+    attacks
+}
+
+/// squares a white piece attacks
+fn attacks(piece: Piece, square: u8, occ: u64) -> u64 {
+    match piece {
+        Piece::Pawn => steps(square, &[(-1, 1), (1, 1)]).fold(0, |a, s| a | bit(s)),
+        Piece::Knight => steps(square, &KNIGHT_STEPS).fold(0, |a, s| a | bit(s)),
+        Piece::Bishop => rays(square, &DIAGONAL, occ),
+        Piece::Rook => rays(square, &LEVEL, occ),
+        Piece::Queen => rays(square, &DIAGONAL, occ) | rays(square, &LEVEL, occ),
+        Piece::King => steps(square, &KING_STEPS).fold(0, |a, s| a | bit(s)),
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Move;
+
+    /// the board of a table index, white is the strong side
+    fn board_of(pos: &Position, material: Material) -> (Board, Side) {
+        let square = |square: u8| BoardPos::from_usize(square as usize).unwrap();
+        let mut board = Board::parse_fen("8/8/8/8/8/8/8/8").unwrap();
+        board.set_piece(Side::Black, Piece::King, square(pos.bk));
+        for (piece, at) in pos.strong_pieces(material) {
+            board.set_piece(Side::White, piece, square(at));
+        }
+        let side = if pos.strong { Side::White } else { Side::Black };
+        (board, side)
+    }
+
+    fn index_of(board: &Board, side: Side, material: Material) -> usize {
+        let square = |pos: BoardPos| pos.to_usize() as u8;
+        let mut pos = Position {
+            strong: side == Side::White,
+            wk: square(board.king(Side::White).unwrap()),
+            bk: square(board.king(Side::Black).unwrap()),
+            pieces: [0; 2],
+        };
+        for (i, &piece) in material.pieces().iter().enumerate() {
+            let (_, _, at) = board
+                .iter()
+                .find(|&(side, p, _)| side == Side::White && p == piece)
+                .unwrap();
+            pos.pieces[i] = square(at);
+        }
+        pos.pack(material)
+    }
+
+    /// positions after every legal move that keeps the material
+    fn successors(board: &Board, side: Side, material: Material) -> Vec<(Move, usize)> {
+        board
+            .legal_moves(side)
+            .into_iter()
+            .filter(|mv| mv.promotion.is_none())
+            .filter_map(|mv| {
+                let mut board = board.clone();
+                board
+                    .play(mv)
+                    .is_none()
+                    .then(|| (mv, index_of(&board, side.other(), material)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generator_agrees_with_board() {
+        for (material, stride) in [
+            (Material::Kqk, 251),
+            (Material::Krk, 251),
+            (Material::Kpk, 251),
+            (Material::Kbnk, 16411),
+        ] {
+            for index in (0..material.len()).step_by(stride) {
+                let pos = Position::unpack(material, index);
+                if !pos.legal(material) {
+                    continue;
+                }
+                let (board, side) = board_of(&pos, material);
+                let next = successors(&board, side, material);
+
+                let mut unmoves = Vec::new();
+                if pos.strong {
+                    pos.weak_unmoves(material, |prev| unmoves.push(prev.pack(material)));
+                    for (_, next) in next {
+                        let mut back = Vec::new();
+                        Position::unpack(material, next)
+                            .strong_unmoves(material, |prev| back.push(prev.pack(material)));
+                        assert!(back.contains(&index), "{material} {}", board.to_fen(side));
+                    }
+                } else {
+                    assert_eq!(pos.weak_in_check(material), board.in_check(side));
+                    assert_eq!(
+                        pos.weak_moves(material) as usize,
+                        board.legal_moves(side).len(),
+                        "{material} {}",
+                        board.to_fen(side)
+                    );
+                    pos.strong_unmoves(material, |prev| unmoves.push(prev.pack(material)));
+                }
+
+                // every position found backwards reaches this one forwards
+                for prev in unmoves {
+                    let prev = Position::unpack(material, prev);
+                    let (board, side) = board_of(&prev, material);
+                    assert!(
+                        successors(&board, side, material)
+                            .iter()
+                            .any(|&(_, next)| next == index),
+                        "{material} {}",
+                        board.to_fen(side)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn longest_mates() {
+        assert_eq!(DtmTable::generate(Material::Krk).longest_mate(), 16);
+        assert_eq!(DtmTable::generate(Material::Kqk).longest_mate(), 10);
+        assert_eq!(DtmTable::generate(Material::Kbnk).longest_mate(), 33);
+    }
+
+    #[test]
+    fn pawn_endings() {
+        let table = DtmTable::generate(Material::Kpk);
+        let probe = |fen: &str| {
+            let (board, side) = Board::from_fen(fen).unwrap();
+            table.probe(&board, side)
+        };
+
+        // the king in front of its pawn on the sixth rank wins either way
+        assert!(matches!(
+            probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(Dtm::Mate(_))
+        ));
+        assert!(matches!(
+            probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Mated(_))
+        ));
+        assert_eq!(probe("k7/2P5/1K6/8/8/8/8/8 w - - 0 1"), Some(Dtm::Mate(1)));
+        // 1. Kg6 Kg8 2. e8=Q#
+        assert_eq!(probe("7k/4P3/5K2/8/8/8/8/8 w - - 0 1"), Some(Dtm::Mate(3)));
+        // a queen would stalemate, a rook mates
+        assert_eq!(probe("8/k1P5/2K5/8/8/8/8/8 w - - 0 1"), Some(Dtm::Mate(3)));
+        assert_eq!(probe("8/6P1/8/8/8/8/8/k1K5 w - - 0 1"), Some(Dtm::Mate(5)));
+
+        // stalemate, and a rook pawn with the king in the corner
+        assert_eq!(probe("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(probe("k7/8/8/8/8/8/P7/7K w - - 0 1"), Some(Dtm::Draw));
+        // black takes the pawn
+        assert_eq!(probe("8/8/8/8/8/8/3kP3/7K b - - 0 1"), Some(Dtm::Draw));
+        // black as the strong side
+        assert!(matches!(
+            probe("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"),
+            Some(Dtm::Mate(_))
+        ));
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        let table = DtmTable::generate(Material::Kqk);
+        let mut bytes = Vec::new();
+        table.write(&mut bytes).unwrap();
+        assert!(bytes.len() < table.dtm.len());
+        let read = DtmTable::read(bytes.as_slice()).unwrap();
+        assert_eq!(read, table);
+
+        let probe = |fen: &str| {
+            let (board, side) = Board::from_fen(fen).unwrap();
+            read.probe(&board, side)
+        };
+        assert_eq!(probe("k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), Some(Dtm::Mate(1)));
+        assert_eq!(probe("k6Q/8/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Mated(0)));
+        // black as the strong side
+        assert_eq!(probe("8/7q/8/8/8/1k6/8/K7 b - - 0 1"), Some(Dtm::Mate(1)));
+        // stalemate
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        assert_eq!(probe("8/8/8/8/8/8/8/K1k4R w - - 0 1"), None);
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(DtmTable::read(bytes.as_slice()).is_err());
+    }
+}
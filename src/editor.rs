@@ -0,0 +1,83 @@
+use chess::{
+    piece::{Piece, Side},
+    Board, BoardPos,
+};
+use srs2dge::glam::Vec2;
+
+//
+
+/// position setup, the app board is the one being edited
+#[derive(Debug, Clone)]
+pub struct Editor {
+    /// the position to go back to when the setup is thrown away
+    pub previous: (Board, Side),
+    /// `K`, `Q`, `k` and `q`
+    pub castling: [bool; 4],
+    /// piece being dragged and the square it was picked from
+    pub dragging: Option<(Side, Piece, Option<BoardPos>)>,
+}
+
+//
+
+/// pieces in the palette, a column per side
+pub const PALETTE: [Piece; 6] = [
+    Piece::King,
+    Piece::Queen,
+    Piece::Rook,
+    Piece::Bishop,
+    Piece::Knight,
+    Piece::Pawn,
+];
+
+/// size of a palette piece
+pub const PALETTE_SIZE: f32 = 0.2;
+
+impl Editor {
+    /// starts editing `board`, every castling right is on
+    pub fn new(board: Board, side: Side) -> Self {
+        Self {
+            previous: (board, side),
+            castling: [true; 4],
+            dragging: None,
+        }
+    }
+
+    pub fn toggle_castling(&mut self, i: usize) {
+        if let Some(right) = self.castling.get_mut(i) {
+            *right = !*right;
+        }
+    }
+
+    /// `board` with the castling rights that are on, rights whose king or
+    /// rook left home are dropped
+    pub fn with_castling(&self, board: &Board) -> Board {
+        let mut board = board.clone();
+        board.set_castling(self.castling);
+        board
+    }
+
+    /// the fen castling field of [`Editor::with_castling`]
+    pub fn castling_field(&self, board: &Board) -> String {
+        let fen = self.with_castling(board).to_fen(Side::White);
+        fen.split(' ').nth(2).unwrap_or("-").to_string()
+    }
+}
+
+/// top left corner of a palette piece, left of the board
+pub fn palette_pos(side: Side, i: usize) -> Vec2 {
+    Vec2::new(
+        -1.04 - PALETTE_SIZE - (1 - side as usize) as f32 * 0.24,
+        i as f32 * 0.25 - 0.75,
+    )
+}
+
+/// the palette piece under `pos`
+pub fn palette_hit(pos: Vec2) -> Option<(Side, Piece)> {
+    [Side::White, Side::Black].into_iter().find_map(|side| {
+        PALETTE.iter().enumerate().find_map(|(i, &piece)| {
+            let corner = palette_pos(side, i);
+            let inside = pos.cmpge(corner).all() && pos.cmplt(corner + PALETTE_SIZE).all();
+            inside.then_some((side, piece))
+        })
+    })
+}
@@ -0,0 +1,154 @@
+use crate::{
+    board::Move,
+    piece::Side,
+    search::{Search, SearchOptions, SearchResult},
+    time::{Limits, Pondering, Stop},
+    Board,
+};
+use std::thread::{self, JoinHandle};
+
+//
+
+/// runs the search on a background thread so a render loop can poll it
+/// without blocking
+pub struct Thinker {
+    /// `None` while a search thread owns it
+    search: Option<Search>,
+    thinking: Option<Thinking>,
+}
+
+struct Thinking {
+    handle: JoinHandle<(Search, SearchResult)>,
+    stop: Stop,
+    pondering: Pondering,
+    /// the opponent move the search assumed
+    ponder_move: Option<Move>,
+}
+
+//
+
+impl Thinker {
+    pub fn new(options: SearchOptions) -> Self {
+        Self {
+            search: Some(Search::new(options)),
+            thinking: None,
+        }
+    }
+
+    /// starts searching for the best move of `side`
+    pub fn think(&mut self, board: &Board, side: Side, limits: Limits) {
+        self.start(board.clone(), side, limits, None);
+    }
+
+    /// starts searching the position after the opponent plays `expected`
+    /// from `board`
+    ///
+    /// the `limits` only start to count when [`Thinker::opponent_moved`]
+    /// confirms the guess
+    pub fn ponder(&mut self, board: &Board, opponent: Side, expected: Move, limits: Limits) {
+        let mut board = board.clone();
+        board.play(expected);
+        self.start(board, opponent.other(), limits, Some(expected));
+    }
+
+    /// tells a running ponder search what the opponent actually played,
+    /// `true` on a ponder hit, otherwise the search is thrown away
+    pub fn opponent_moved(&mut self, mv: Move) -> bool {
+        let hit = match self.thinking.as_ref() {
+            Some(thinking) => thinking.ponder_move == Some(mv),
+            None => return false,
+        };
+
+        if hit {
+            log::debug!("ponder hit {mv}");
+            self.thinking.as_ref().unwrap().pondering.hit();
+        } else {
+            log::debug!("ponder miss {mv}");
+            self.stop();
+        }
+        hit
+    }
+
+    /// the finished search, `None` while still thinking or pondering
+    pub fn poll(&mut self) -> Option<SearchResult> {
+        let thinking = self.thinking.as_ref()?;
+        if !thinking.handle.is_finished() || thinking.pondering.is_pondering() {
+            return None;
+        }
+
+        let thinking = self.thinking.take().unwrap();
+        let (search, result) = thinking.handle.join().expect("search thread panicked");
+        self.search = Some(search);
+        Some(result)
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.thinking.is_some()
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.thinking
+            .as_ref()
+            .is_some_and(|thinking| thinking.pondering.is_pondering())
+    }
+
+    /// stops and discards the running search
+    pub fn stop(&mut self) {
+        if let Some(thinking) = self.thinking.take() {
+            thinking.stop.stop();
+            let (search, _) = thinking.handle.join().expect("search thread panicked");
+            self.search = Some(search);
+        }
+    }
+
+    /// the idle search, stops a running one first
+    pub fn search(&mut self) -> &mut Search {
+        self.stop();
+        self.search.as_mut().unwrap()
+    }
+
+    fn start(&mut self, board: Board, side: Side, limits: Limits, ponder_move: Option<Move>) {
+        self.stop();
+
+        let search = self.search.take().unwrap();
+        let stop = Stop::new();
+        let pondering = Pondering::new(ponder_move.is_some());
+
+        let thread_stop = stop.clone();
+        let thread_pondering = pondering.clone();
+        let handle = thread::Builder::new()
+            .name("thinker".to_string())
+            .spawn(move || {
+                let result = search.go_ponder(
+                    &board,
+                    side,
+                    limits,
+                    thread_stop,
+                    thread_pondering,
+                    |info| {
+                        log::debug!(
+                            "depth {} score {} nodes {}",
+                            info.depth,
+                            info.score,
+                            info.nodes
+                        )
+                    },
+                );
+                (search, result)
+            })
+            .expect("failed to spawn the search thread");
+
+        self.thinking = Some(Thinking {
+            handle,
+            stop,
+            pondering,
+            ponder_move,
+        });
+    }
+}
+
+impl Drop for Thinker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{
+    piece::{Piece, Side},
+    Board,
+};
+
+//
+
+pub const MATE: i32 = 30_000;
+pub const MATE_BOUND: i32 = MATE - 1_000;
+
+/// tablebase wins score below every mate
+pub const TB_WIN: i32 = MATE_BOUND - 1_000;
+
+//
+
+/// static evaluation from the point of view of `side`
+pub fn evaluate(board: &Board, side: Side) -> i32 {
+    board
+        .iter()
+        .map(|(s, piece, pos)| {
+            let file = pos.file as i32;
+            let rank = pos.rank as i32;
+
+            // distance from the center, 0..=6
+            let center = (2 * file - 9).abs() / 2 + (2 * rank - 9).abs() / 2;
+            let placement = match piece {
+                Piece::Pawn => {
+                    let advance = match s {
+                        Side::White => rank - 2,
+                        Side::Black => 7 - rank,
+                    };
+                    advance * 8 + (6 - center) * 2
+                }
+                Piece::Knight | Piece::Bishop => (6 - center) * 5,
+                Piece::Rook | Piece::Queen => (6 - center) * 2,
+                Piece::King => center * 3,
+            };
+
+            let score = piece.value() + placement;
+            if s == side {
+                score
+            } else {
+                -score
+            }
+        })
+        .sum()
+}
+
+pub const fn is_mate(score: i32) -> bool {
+    score.abs() >= MATE_BOUND
+}
@@ -0,0 +1,163 @@
+use chess::{board::FenError, tree::GameTree, Board};
+use core::fmt;
+
+//
+
+/// a line of typed or pasted text, the window has no clipboard access
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    pub text: String,
+    /// why the text could not be loaded, shown until the next edit
+    pub error: Option<InputError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    Fen(FenError),
+    /// not a single tag or playable move
+    NoMoves,
+}
+
+//
+
+/// characters of the text that fit on the board
+const VISIBLE: usize = 47;
+
+//
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a typed character, control characters are left to the key events
+    pub fn push(&mut self, c: char) {
+        if c.is_control() {
+            return;
+        }
+        self.text.push(c);
+        self.error = None;
+    }
+
+    pub fn pop(&mut self) {
+        self.text.pop();
+        self.error = None;
+    }
+
+    /// the end of the text with a cursor
+    pub fn visible(&self) -> String {
+        let skip = self.text.chars().count().saturating_sub(VISIBLE);
+        let mut visible = self.text.chars().skip(skip).collect::<String>();
+        visible.push('_');
+        visible
+    }
+
+    /// a fen position or a pgn game, a bare move list included
+    pub fn parse(&self) -> Result<GameTree, InputError> {
+        let text = self.text.trim();
+
+        // the placement field of a fen has its ranks split by `/`
+        let first = text.split_whitespace().next().unwrap_or_default();
+        if first.contains('/') && !text.starts_with('[') {
+            let (board, side) = Board::from_fen(text).map_err(InputError::Fen)?;
+            return Ok(GameTree::new(board, side));
+        }
+
+        match GameTree::parse(text) {
+            Some(tree) if !tree.tags.is_empty() || !tree.children.is_empty() => Ok(tree),
+            _ => Err(InputError::NoMoves),
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Fen(err) => write!(f, "invalid fen: {err}"),
+            InputError::NoMoves => f.write_str("no fen and no playable move"),
+        }
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::{board::Move, pgn::GameResult, piece::Side};
+
+    fn parse(text: &str) -> Result<GameTree, InputError> {
+        TextInput {
+            text: text.to_string(),
+            error: None,
+        }
+        .parse()
+    }
+
+    fn moves(line: &str) -> Vec<Move> {
+        line.split(' ').map(|mv| Move::parse(mv).unwrap()).collect()
+    }
+
+    #[test]
+    fn typed_characters() {
+        // committed ime text is typed like any other character
+        let mut input = TextInput::new();
+        for c in "e4 é\u{8}\r".chars() {
+            input.push(c);
+        }
+        assert_eq!(input.text, "e4 é");
+        input.pop();
+        assert_eq!(input.text, "e4 ");
+    }
+
+    #[test]
+    fn fen_positions() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1";
+        let tree = parse(&format!("  {fen} ")).unwrap();
+        assert_eq!(
+            (tree.board.clone(), tree.side),
+            Board::from_fen(fen).unwrap()
+        );
+        assert_eq!(tree.side, Side::Black);
+        assert!(tree.tags.is_empty() && tree.children.is_empty());
+
+        assert_eq!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").unwrap_err(),
+            InputError::Fen(FenError::Side("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn pgn_games() {
+        let tree = parse(
+            "[Event \"casual\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n\
+             {a pawn up} 1. e4 {the long step} Kd7 2. e5 1-0",
+        )
+        .unwrap();
+        assert_eq!(tree.tag("Event"), Some("casual"));
+        assert_eq!(
+            tree.board.to_fen(tree.side),
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"
+        );
+        assert_eq!(tree.comment.as_deref(), Some("a pawn up"));
+        assert_eq!(tree.children[0].comment.as_deref(), Some("the long step"));
+        assert_eq!(tree.main_line(&[]), moves("e2e4 e8d7 e4e5"));
+        assert_eq!(tree.result, GameResult::WhiteWins);
+    }
+
+    #[test]
+    fn bare_move_lists() {
+        // the line ends at the first illegal move
+        let tree = parse("1. e4 e5 2. Nf3 Nc6 3. Qxf7 Nf6").unwrap();
+        assert!(tree.tags.is_empty());
+        assert_eq!(tree.main_line(&[]), moves("e2e4 e7e5 g1f3 b8c6"));
+
+        for nothing in ["", "  ", "Ke2 e5", "hello"] {
+            assert_eq!(
+                parse(nothing).unwrap_err(),
+                InputError::NoMoves,
+                "{nothing}"
+            );
+        }
+    }
+}
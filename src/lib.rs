@@ -0,0 +1,24 @@
+pub use board::{Board, BoardPos};
+
+//
+
+pub mod board;
+pub mod book;
+pub mod clock;
+pub mod dfpn;
+pub mod dtm;
+pub mod engine;
+pub mod eval;
+pub mod mate;
+pub mod pgn;
+pub mod piece;
+pub mod search;
+pub mod sprt;
+pub mod strength;
+pub mod tablebase;
+pub mod time;
+pub mod tournament;
+pub mod tree;
+pub mod tt;
+pub mod uci;
+pub mod uci_client;
@@ -1,7 +1,24 @@
-use board::{Board, BoardPos};
+use chess::{
+    board::Move,
+    book::{Book, Selection},
+    clock::{can_mate, format_time, Clock, TimeControl},
+    engine::Thinker,
+    eval::{is_mate, MATE},
+    pgn::{to_san, GameResult},
+    piece::{Piece, Side},
+    search::SearchOptions,
+    strength::Difficulty,
+    tablebase::{Tablebase, Wdl},
+    time::Limits,
+    tree::{nag_glyph, GameTree},
+    uci_client::UciEngine,
+    Board, BoardPos,
+};
+use editor::{palette_hit, palette_pos, Editor, PALETTE, PALETTE_SIZE};
+use input::TextInput;
 use main_game_loop::{AnyEngine, Event, GameLoop, Runnable, WinitEvent};
-use piece::{Piece, Side};
-use rand::prelude::{IteratorRandom, SliceRandom, ThreadRng};
+use move_list::MoveList;
+use player::{Player, PlayerArgs};
 use ron::ser::PrettyConfig;
 use srs2dge::{
     batch::{quad::QuadMesh, BatchRenderer, Idx},
@@ -19,16 +36,29 @@ use srs2dge::{
     program::{color_2d_program, texture_2d_program, DefaultVertex},
     BuildEngine, Engine,
 };
-use std::{fs::File, process::exit};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use text::Text;
 use winit::{
-    event::{ElementState, MouseButton, WindowEvent},
+    event::{
+        ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+    },
     window::WindowBuilder,
 };
 
 //
 
-mod board;
-mod piece;
+mod editor;
+mod input;
+mod move_list;
+mod player;
+mod text;
 
 //
 
@@ -40,91 +70,1508 @@ macro_rules! load_png {
     };
 }
 
-//
+const AI_MOVE_TIME: Duration = Duration::from_secs(1);
+
+/// polyglot book the AI opens with, if the file exists
+const BOOK_PATH: &str = "book.bin";
+
+/// directory with syzygy tables, if it exists
+const SYZYGY_PATH: &str = "syzygy";
+
+/// height of a line of text
+const TEXT_SIZE: f32 = 0.08;
+
+/// autoplay step interval bounds
+const MIN_REPLAY_INTERVAL: Duration = Duration::from_millis(125);
+const MAX_REPLAY_INTERVAL: Duration = Duration::from_secs(8);
+
+/// file in the games directory with the unfinished game of the last session
+const AUTOSAVE: &str = "autosave.pgn";
+
+/// tag of the autosave with the tree path of the position the game was left
+/// at
+const CURRENT_PATH: &str = "CurrentPath";
+
+/// time controls the menu cycles through
+const TIME_CONTROLS: [&str; 5] = ["1+0", "3+2", "5d3", "15+10", "40/90+30,30+30"];
+
+//
+
+struct App {
+    color_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
+    tex_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
+    circle_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
+    text_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
+    color_program: Program,
+    tex_program: Program,
+    circle_program: Program,
+    texture: TextureAtlasMap<(Side, Piece)>,
+
+    turn: Side,
+
+    circle_quads: [Idx; 64],
+    piece_quads: [Idx; 64],
+    /// the editor palette, white then black in `PALETTE` order
+    palette_quads: [Idx; 12],
+    hand_quad: Idx,
+    board: Board,
+
+    cursor: Option<(BoardPos, Vec2)>,
+    /// cursor in the coordinates of the quads
+    mouse: Vec2,
+    moving: Option<(BoardPos, Side, Piece)>,
+    /// the picked piece is held down, `true` if it was picked before the
+    /// press
+    dragging: Option<bool>,
+    /// black at the bottom
+    flipped: bool,
+
+    /// played moves and what they captured
+    history: Vec<(Move, Option<(Side, Piece)>)>,
+    /// every move tried, undone ones and variations included
+    tree: GameTree,
+    /// of the current position in `tree`, as long as `history`
+    path: Vec<usize>,
+    move_list: MoveList,
+
+    /// indexed by side
+    players: [Player; 2],
+    /// external engines the menu offers
+    engines: Vec<String>,
+    thinkers: [Thinker; 2],
+    /// running external engines
+    externals: [Option<UciEngine>; 2],
+    analyser_path: Option<String>,
+    analyser: Option<UciEngine>,
+    /// hash of the position the analyser is looking at
+    analysed: Option<u64>,
+    analysis: String,
+    analysis_text: Text,
+    ponder: bool,
+    book: Option<Book>,
+    tablebase: Option<Arc<Tablebase>>,
+    /// tablebase result of the current position
+    tablebase_text: Text,
+
+    time_control: Option<TimeControl>,
+    clock: Option<Clock>,
+    clock_text: [Text; 2],
+    /// what `clock_text` shows, indexed by side
+    clock_shown: [(String, Vec4); 2],
+
+    /// players can only be changed before the game starts
+    started: bool,
+    menu_text: [Text; 2],
+    result: Option<GameResult>,
+    result_text: Text,
+
+    /// stepping through the game, moves only come from the history
+    replaying: bool,
+    /// when autoplay makes the next step
+    autoplay: Option<Instant>,
+    replay_interval: Duration,
+    replay_text: Text,
+
+    /// where games and positions are saved
+    games_dir: PathBuf,
+
+    /// setting up a position instead of playing
+    editor: Option<Editor>,
+
+    /// a fen or pgn being typed, takes every key
+    input: Option<TextInput>,
+    /// darkens the board under the input
+    input_quad: Idx,
+    /// help, text and error
+    input_text: [Text; 3],
+}
+
+//
+
+impl App {
+    fn update_batch(&mut self) {
+        // log::debug!("board = {:?}", self.board);
+        for pos in BoardPos::iter() {
+            let idx = self.piece_quads[pos.to_usize()];
+            let Some((side, piece)) = self.board.get_piece(&pos) else {
+                // castling and en passant empty more than the square moved from
+                self.tex_batcher.get_mut(idx).col.w = 0.0;
+                continue;
+            };
+
+            // log::debug!("{side:?} {piece:?} at {pos}");
+
+            let tex = self.piece_tex(side, piece);
+            let square = self.square_quad(pos);
+            let quad = self.tex_batcher.get_mut(idx);
+            quad.size = Vec2::new(0.25, 0.25);
+            quad.pos = square;
+            quad.col = Vec4::new(1.0, 1.0, 1.0, 1.0);
+            quad.tex = tex;
+        }
+
+        if let Some((pos, side, _)) = self.moving.as_ref() {
+            // piece picked up, a held one is drawn by the hand
+            let held = self.dragging.is_some();
+            let quad = self.tex_batcher.get_mut(self.piece_quads[pos.to_usize()]);
+            if held {
+                quad.col.w = 0.3;
+            } else {
+                quad.pos -= 0.02;
+                quad.size += 0.04;
+            }
+
+            // piece legal moves
+            for piece in BoardPos::iter() {
+                let idx = self.circle_quads[piece.to_usize()];
+                if (self.circle_batcher.get(idx).col.w).abs() >= std::f32::EPSILON {
+                    self.circle_batcher.get_mut(idx).col.w = 0.0;
+                }
+            }
+            for piece in self.targets(*pos, *side) {
+                if self.board.get_piece(&piece).is_some() {
+                    // if it is a capture
+                    // draw a frame around it
+                    let idx = self.circle_quads[piece.to_usize()];
+                    if self.circle_batcher.get(idx).col.w >= std::f32::EPSILON - 1.0 {
+                        self.circle_batcher.get_mut(idx).col.w = -1.0;
+                    }
+                } else {
+                    // if it is just a move
+                    // draw a dot in it
+                    let idx = self.circle_quads[piece.to_usize()];
+                    if self.circle_batcher.get(idx).col.w <= 1.0 - std::f32::EPSILON {
+                        self.circle_batcher.get_mut(idx).col.w = 1.0;
+                    }
+                }
+            }
+        } else {
+            for piece in BoardPos::iter() {
+                let idx = self.circle_quads[piece.to_usize()];
+                if (self.circle_batcher.get(idx).col.w).abs() >= std::f32::EPSILON {
+                    self.circle_batcher.get_mut(idx).col.w = 0.0;
+                }
+            }
+        }
+    }
+
+    /// squares the piece on `from` can legally go to
+    fn targets(&self, from: BoardPos, side: Side) -> Vec<BoardPos> {
+        self.board
+            .legal_moves(side)
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .map(|mv| mv.to)
+            .collect()
+    }
+
+    /// the legal move from `from` to `to`, promotions come queen first so a
+    /// human always promotes to a queen
+    fn human_move(&self, from: BoardPos, to: BoardPos, side: Side) -> Option<Move> {
+        self.board
+            .legal_moves(side)
+            .into_iter()
+            .find(|mv| mv.from == from && mv.to == to)
+    }
+
+    /// top left corner of the quad on `pos`
+    fn square_quad(&self, pos: BoardPos) -> Vec2 {
+        let (u, v) = if self.flipped {
+            (8 - pos.file, 8 - pos.rank)
+        } else {
+            (pos.file - 1, pos.rank - 1)
+        };
+        Vec2::new(u as f32 * 0.25 - 1.0, -(v as f32 * 0.25 - 0.75))
+    }
+
+    /// the side drawn at the bottom
+    fn bottom(&self) -> Side {
+        if self.flipped {
+            Side::Black
+        } else {
+            Side::White
+        }
+    }
+
+    fn set_flipped(&mut self, flipped: bool) {
+        if self.flipped == flipped {
+            return;
+        }
+        self.flipped = flipped;
+        for pos in BoardPos::iter() {
+            let square = self.square_quad(pos);
+            self.circle_batcher
+                .get_mut(self.circle_quads[pos.to_usize()])
+                .pos = square;
+        }
+        self.update_batch();
+
+        // the clocks swap places too
+        self.clock_shown = Default::default();
+        self.update_clock_text();
+    }
+
+    /// atlas position of a piece sprite, flipped for the y down quads
+    fn piece_tex(&self, side: Side, piece: Piece) -> TexturePosition {
+        let mut tex = *self.texture.get(&(side, piece)).unwrap();
+        tex.top_left.y = 1.0 - tex.top_left.y;
+        tex.bottom_right.y = 1.0 - tex.bottom_right.y;
+        tex
+    }
+
+    /// plays a move of the side to move, whoever made it
+    fn play_move(&mut self, mv: Move) {
+        if let Some(clock) = self.clock.as_mut() {
+            clock.press(self.turn);
+        }
+        self.make_move(mv);
+        self.thinkers[self.turn as usize].opponent_moved(mv);
+        self.update_tablebase_text();
+        self.update_move_list();
+    }
+
+    /// the board, tree and sprite part of a move, a move not in the tree
+    /// starts a variation
+    fn make_move(&mut self, mv: Move) {
+        if self.tree.play(&mut self.path, mv) && self.path.last() != Some(&0) {
+            log::info!("new variation");
+        }
+        let captured = self.board.play(mv);
+        self.history.push((mv, captured));
+        self.update_batch();
+        self.turn = self.turn.other();
+    }
+
+    /// takes back the last move, `false` at the start of the game
+    fn unmake_move(&mut self) -> bool {
+        let (mv, captured) = match self.history.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        self.board.unplay(mv, captured);
+        self.path.pop();
+        self.update_batch();
+        self.turn = self.turn.other();
+        true
+    }
+
+    /// takes moves back until a human is to move, against an engine that
+    /// is its reply and the human's own move
+    fn undo(&mut self) {
+        if !self.can_step() {
+            return;
+        }
+        let mut undone = false;
+        while self.unmake_move() {
+            undone = true;
+            if self.players[self.turn as usize].is_human() {
+                break;
+            }
+        }
+        if undone {
+            self.after_step();
+        }
+    }
+
+    /// plays undone moves again until a human is to move
+    fn redo(&mut self) {
+        if !self.can_step() {
+            return;
+        }
+        let mut redone = false;
+        while let Some(mv) = self.next_move() {
+            self.make_move(mv);
+            redone = true;
+            if self.players[self.turn as usize].is_human() {
+                break;
+            }
+        }
+        if redone {
+            self.after_step();
+        }
+    }
+
+    /// goes back or forward to the position after `plies` moves
+    fn jump(&mut self, plies: usize) {
+        if plies == self.history.len() || !(self.replaying || self.can_step()) {
+            return;
+        }
+        self.seek(plies);
+    }
+
+    /// `jump` without asking anyone
+    fn seek(&mut self, plies: usize) {
+        self.stop_thinking();
+        self.moving = None;
+        while self.history.len() > plies && self.unmake_move() {}
+        while self.history.len() < plies {
+            match self.next_move() {
+                Some(mv) => self.make_move(mv),
+                None => break,
+            }
+        }
+        self.after_step();
+    }
+
+    /// the main line move after the current position
+    fn next_move(&self) -> Option<Move> {
+        self.tree
+            .children(&self.path)
+            .and_then(|children| children.first())
+            .map(|node| node.mv)
+    }
+
+    /// swaps the current move for the next or previous one tried from the
+    /// same position
+    fn switch_variation(&mut self, delta: isize) {
+        let (&i, parent) = match self.path.split_last() {
+            Some(last) => last,
+            None => return,
+        };
+        let siblings = self.tree.children(parent).map_or(0, <[_]>::len);
+        let j = (i as isize + delta).rem_euclid(siblings as isize) as usize;
+        if j == i {
+            return;
+        }
+        let mv = self.tree.children(parent).expect("valid path")[j].mv;
+        self.stop_thinking();
+        self.moving = None;
+        self.unmake_move();
+        self.make_move(mv);
+        self.after_step();
+    }
+
+    /// makes the current variation the main line one step at a time
+    fn promote_variation(&mut self) {
+        if self.tree.promote(&mut self.path) {
+            self.update_move_list();
+            self.update_replay_text();
+        }
+    }
+
+    fn annotate(&mut self, nag: u8) {
+        self.tree.toggle_nag(&self.path, nag);
+        self.update_move_list();
+    }
+
+    /// removes the current variation and goes back to where it started
+    fn delete_variation(&mut self) {
+        let plies = match self.tree.delete(&self.path) {
+            Some(plies) => plies,
+            None => return,
+        };
+        self.stop_thinking();
+        self.moving = None;
+        while self.history.len() > plies && self.unmake_move() {}
+        self.after_step();
+    }
+
+    /// undo and redo stop at human moves, without any there is nothing to
+    /// stop at
+    fn can_step(&mut self) -> bool {
+        if !self.players.iter().any(Player::is_human) {
+            log::debug!("no human to take moves back for");
+            return false;
+        }
+        // stepping back out of a finished game plays on from there
+        if self.result.take().is_some() {
+            self.result_text.clear(&mut self.text_batcher);
+            self.replaying = false;
+            self.autoplay = None;
+        }
+        self.stop_thinking();
+        // a picked up piece never left the board
+        self.moving = None;
+        true
+    }
+
+    fn after_step(&mut self) {
+        self.update_batch();
+        self.update_tablebase_text();
+        self.update_move_list();
+        self.update_replay_text();
+        // the time spent on the taken back moves stays spent
+        if let Some(clock) = self.clock.as_mut().filter(|_| !self.replaying) {
+            clock.start(self.turn);
+        }
+    }
+
+    /// the played moves and their main line continuation in san
+    fn update_move_list(&mut self) {
+        let mut board = self.tree.board.clone();
+        let mut side = self.tree.side;
+
+        let mut nodes = (1..=self.path.len())
+            .filter_map(|i| self.tree.node(&self.path[..i]))
+            .collect::<Vec<_>>();
+        let mut children = self.tree.children(&self.path).unwrap_or_default();
+        while let Some(node) = children.first() {
+            nodes.push(node);
+            children = &node.children;
+        }
+
+        let mut sans = Vec::new();
+        for node in nodes {
+            let mut san = to_san(&board, side, node.mv);
+            san.extend(node.nags.iter().filter_map(|&nag| nag_glyph(nag)));
+            sans.push(san);
+            board.play(node.mv);
+            side = side.other();
+        }
+        self.move_list.set(
+            &mut self.text_batcher,
+            sans,
+            self.tree.side,
+            self.tree.board.fullmove(),
+            self.history.len(),
+        );
+    }
+
+    /// stops every engine search, pondering included
+    fn stop_thinking(&mut self) {
+        for thinker in self.thinkers.iter_mut() {
+            thinker.stop();
+        }
+        for engine in self.externals.iter_mut().flatten() {
+            if let Err(err) = engine.stop() {
+                log::error!("{}: {err}", engine.name());
+            }
+        }
+    }
+
+    /// the built in search plays `side`
+    fn update_engine(&mut self, side: Side, difficulty: Difficulty) {
+        let limits = self.limits(side);
+        let thinker = &mut self.thinkers[side as usize];
+        if !thinker.is_thinking() {
+            let book_move = self
+                .book
+                .as_ref()
+                .filter(|_| difficulty != Difficulty::Random)
+                .and_then(|book| book.pick(&self.board, side, Selection::Weighted));
+            if let Some(mv) = book_move {
+                log::info!("book move {mv}");
+                self.play_move(mv);
+                return;
+            }
+
+            thinker.think(&self.board, side, limits);
+        }
+
+        let result = match thinker.poll() {
+            Some(result) => result,
+            None => return,
+        };
+        // the game ends before a side without moves gets to think
+        let mv = match result.best {
+            Some(mv) => mv,
+            None => return,
+        };
+        self.play_move(mv);
+
+        // think about the expected reply while the opponent does
+        if let (true, Some(&reply)) = (self.ponder, result.pv.get(1)) {
+            let limits = self.limits(side);
+            self.thinkers[side as usize].ponder(&self.board, side.other(), reply, limits);
+        }
+    }
+
+    /// the clock of `side` or a fixed time per move without clocks
+    fn limits(&self, side: Side) -> Limits {
+        self.clock
+            .as_ref()
+            .map_or(Limits::move_time(AI_MOVE_TIME), |clock| clock.limits(side))
+    }
+
+    /// an external uci engine plays `side`
+    fn update_external(&mut self, side: Side) {
+        let limits = self.limits(side);
+        let opponent_time = self
+            .clock
+            .as_ref()
+            .map(|clock| clock.remaining(side.other()));
+        let engine = match self.externals[side as usize].as_mut() {
+            Some(engine) => engine,
+            None => return,
+        };
+
+        let result = if engine.is_thinking() {
+            engine.poll()
+        } else {
+            let moves = self.history.iter().map(|&(mv, _)| mv).collect::<Vec<_>>();
+            engine
+                .go(
+                    &self.tree.board,
+                    self.tree.side,
+                    &moves,
+                    limits,
+                    opponent_time,
+                )
+                .map(|_| None)
+        };
+        let best = match result {
+            Ok(Some(best)) => best,
+            Ok(None) => return,
+            Err(err) => {
+                // a human takes over
+                log::error!("{}: {err}", engine.name());
+                self.externals[side as usize] = None;
+                self.players[side as usize] = Player::Human;
+                return;
+            }
+        };
+
+        match best
+            .mv
+            .filter(|mv| self.board.legal_moves(side).contains(mv))
+        {
+            Some(mv) => self.play_move(mv),
+            None => {
+                log::error!("{} played an illegal move", engine.name());
+                self.externals[side as usize] = None;
+                self.players[side as usize] = Player::Human;
+            }
+        }
+    }
+
+    /// keeps the analyser on the current position and shows its best line
+    fn update_analysis(&mut self) {
+        let engine = match self.analyser.as_mut() {
+            Some(engine) => engine,
+            None => return,
+        };
+
+        let key = self.board.hash(self.turn);
+        let result = if self.analysed != Some(key) {
+            self.analysed = Some(key);
+            let moves = self.history.iter().map(|&(mv, _)| mv).collect::<Vec<_>>();
+            engine
+                .analyse(&self.tree.board, self.tree.side, &moves)
+                .map(|_| None)
+        } else {
+            engine.poll()
+        };
+        if let Err(err) = result {
+            log::error!("{}: {err}", engine.name());
+            self.analyser = None;
+            self.analysis_text.clear(&mut self.text_batcher);
+            return;
+        }
+
+        let info = match engine.infos().first() {
+            Some(info) => info,
+            None => return,
+        };
+        // from white's point of view
+        let score = match self.turn {
+            Side::White => info.score,
+            Side::Black => -info.score,
+        };
+        let eval = if is_mate(score) {
+            let moves = (MATE - score.abs() + 1) / 2;
+            format!("#{}{moves}", if score > 0 { "" } else { "-" })
+        } else {
+            format!("{:+.2}", score as f32 / 100.0)
+        };
+        let mut board = self.board.clone();
+        let mut side = self.turn;
+        let mut pv = Vec::new();
+        for &mv in info.pv.iter().take(4) {
+            pv.push(to_san(&board, side, mv));
+            board.play(mv);
+            side = side.other();
+        }
+
+        let analysis = format!("{eval} d{} {}", info.depth, pv.join(" "));
+        if analysis != self.analysis {
+            self.analysis_text.set(
+                &mut self.text_batcher,
+                &analysis,
+                Vec2::new(-1.0, 1.0 + TEXT_SIZE * 0.5),
+                TEXT_SIZE,
+                Vec4::new(0.8, 0.8, 0.8, 1.0),
+            );
+            self.analysis = analysis;
+        }
+    }
+
+    fn menu_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::W => self.players[0] = self.players[0].next(&self.engines),
+            VirtualKeyCode::B => self.players[1] = self.players[1].next(&self.engines),
+            VirtualKeyCode::T => self.time_control = next_time_control(&self.time_control),
+            VirtualKeyCode::L => {
+                self.load_newest();
+                return;
+            }
+            VirtualKeyCode::E => {
+                self.enter_editor();
+                return;
+            }
+            VirtualKeyCode::X => {
+                self.set_flipped(!self.flipped);
+                return;
+            }
+            VirtualKeyCode::Return => {
+                self.start_game();
+                return;
+            }
+            _ => return,
+        }
+        self.update_menu_text();
+    }
+
+    fn game_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::S => self.save_game(),
+            VirtualKeyCode::F => self.save_position(),
+            VirtualKeyCode::L => self.load_newest(),
+            VirtualKeyCode::E => self.enter_editor(),
+            VirtualKeyCode::X => self.set_flipped(!self.flipped),
+            // a finished game is replayed, but its last moves can be undone
+            VirtualKeyCode::Z | VirtualKeyCode::Back if self.result.is_some() => self.undo(),
+            _ if self.replaying => self.replay_key(key),
+            VirtualKeyCode::Z | VirtualKeyCode::Back => self.undo(),
+            VirtualKeyCode::Y => self.redo(),
+            VirtualKeyCode::R => self.start_replay(),
+            _ => {}
+        }
+    }
+
+    fn replay_key(&mut self, key: VirtualKeyCode) {
+        let plies = self.history.len();
+        match key {
+            VirtualKeyCode::Left => self.seek(plies.saturating_sub(1)),
+            VirtualKeyCode::Right => self.seek(plies + 1),
+            VirtualKeyCode::Home => self.seek(0),
+            VirtualKeyCode::End => self.seek(plies + self.tree.main_line(&self.path).len()),
+            VirtualKeyCode::Up => self.switch_variation(-1),
+            VirtualKeyCode::Down => self.switch_variation(1),
+            VirtualKeyCode::U => self.promote_variation(),
+            VirtualKeyCode::Delete => self.delete_variation(),
+            // ! ? !! ?? !? ?!
+            VirtualKeyCode::Key1 => self.annotate(1),
+            VirtualKeyCode::Key2 => self.annotate(2),
+            VirtualKeyCode::Key3 => self.annotate(3),
+            VirtualKeyCode::Key4 => self.annotate(4),
+            VirtualKeyCode::Key5 => self.annotate(5),
+            VirtualKeyCode::Key6 => self.annotate(6),
+            VirtualKeyCode::Space => {
+                self.autoplay = match self.autoplay {
+                    Some(_) => None,
+                    None => Some(Instant::now() + self.replay_interval),
+                };
+                self.update_replay_text();
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                self.replay_interval = (self.replay_interval * 2).min(MAX_REPLAY_INTERVAL);
+                self.update_replay_text();
+            }
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                self.replay_interval = (self.replay_interval / 2).max(MIN_REPLAY_INTERVAL);
+                self.update_replay_text();
+            }
+            VirtualKeyCode::R | VirtualKeyCode::Return => self.branch(),
+            _ => {}
+        }
+    }
+
+    /// stops the game to step through it
+    fn start_replay(&mut self) {
+        self.stop_thinking();
+        self.moving = None;
+        self.update_batch();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop();
+        }
+        self.replaying = true;
+        self.autoplay = None;
+        log::info!(
+            "replay: [left] [right] step  [home] [end] jump  [space] autoplay  [-] [+] speed  \
+             [up] [down] variation  [u] promote  [delete] delete  [1]-[6] annotate  \
+             [r] play from here"
+        );
+        self.update_replay_text();
+    }
+
+    /// continues the game from the replayed position, the moves after it
+    /// stay in the tree and a different one starts a variation
+    fn branch(&mut self) {
+        self.replaying = false;
+        self.autoplay = None;
+        if self.result.take().is_some() {
+            self.result_text.clear(&mut self.text_batcher);
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(self.turn);
+        }
+        self.update_replay_text();
+    }
+
+    /// steps forward when autoplay is due
+    fn update_autoplay(&mut self) {
+        let due = match self.autoplay {
+            Some(due) => due,
+            None => return,
+        };
+        if Instant::now() < due {
+            return;
+        }
+        if self.next_move().is_none() {
+            self.autoplay = None;
+            self.update_replay_text();
+            return;
+        }
+        self.autoplay = Some(due + self.replay_interval);
+        self.seek(self.history.len() + 1);
+    }
+
+    fn update_replay_text(&mut self) {
+        if !self.replaying {
+            self.replay_text.clear(&mut self.text_batcher);
+            return;
+        }
+        let plies = self.history.len();
+        let mut text = format!(
+            "replay {plies}/{}",
+            plies + self.tree.main_line(&self.path).len()
+        );
+        if let Some((&i, parent)) = self.path.split_last() {
+            let siblings = self.tree.children(parent).map_or(0, <[_]>::len);
+            if siblings > 1 {
+                text += &format!(" var {}/{siblings}", i + 1);
+            }
+        }
+        if self.autoplay.is_some() {
+            text += &format!(" auto {}s", self.replay_interval.as_secs_f32());
+        }
+        self.replay_text.set(
+            &mut self.text_batcher,
+            &text,
+            Vec2::new(1.05, -1.0 - TEXT_SIZE * 1.5),
+            TEXT_SIZE * 0.5,
+            Vec4::new(0.8, 0.8, 0.8, 1.0),
+        );
+    }
+
+    fn start_game(&mut self) {
+        for side in [Side::White, Side::Black] {
+            let i = side as usize;
+            match &self.players[i] {
+                Player::Human => {}
+                Player::Engine(difficulty) => {
+                    self.thinkers[i]
+                        .search()
+                        .set_strength(difficulty.strength());
+                }
+                Player::External(path) => {
+                    let engine = UciEngine::spawn(path).and_then(|mut engine| {
+                        engine.new_game()?;
+                        Ok(engine)
+                    });
+                    match engine {
+                        Ok(engine) => {
+                            log::info!("{} plays {}", engine.name(), side_name(side));
+                            self.externals[i] = Some(engine);
+                        }
+                        Err(err) => {
+                            log::error!("could not start '{path}': {err}");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(path) = self.analyser_path.as_ref() {
+            match UciEngine::spawn(path) {
+                Ok(engine) => {
+                    log::info!("{} analyses the game", engine.name());
+                    self.analyser = Some(engine);
+                }
+                Err(err) => log::error!("could not start '{path}': {err}"),
+            }
+        }
+
+        // a lone human sees the board from their side
+        match (self.players[0].is_human(), self.players[1].is_human()) {
+            (true, false) => self.set_flipped(false),
+            (false, true) => self.set_flipped(true),
+            _ => {}
+        }
+
+        self.clock = self.time_control.clone().map(Clock::new);
+        if let Some(clock) = self.clock.as_mut() {
+            clock.start(self.turn);
+        }
+
+        log::info!("{} vs {}", self.players[0], self.players[1]);
+        self.started = true;
+        for text in self.menu_text.iter_mut() {
+            text.clear(&mut self.text_batcher);
+        }
+        // a loaded game that already ended
+        if self.result.is_some() {
+            self.start_replay();
+        }
+    }
+
+    /// stops the game to set up a position on its board
+    fn enter_editor(&mut self) {
+        self.stop_thinking();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop();
+        }
+        self.moving = None;
+        self.replaying = false;
+        self.autoplay = None;
+        self.update_replay_text();
+        self.started = false;
+
+        self.editor = Some(Editor::new(self.board.clone(), self.turn));
+        for (i, &idx) in self.palette_quads.iter().enumerate() {
+            let side = if i < 6 { Side::White } else { Side::Black };
+            let tex = self.piece_tex(side, PALETTE[i % 6]);
+            let quad = self.tex_batcher.get_mut(idx);
+            quad.pos = palette_pos(side, i % 6);
+            quad.col = Vec4::new(1.0, 1.0, 1.0, 1.0);
+            quad.tex = tex;
+        }
+        log::info!(
+            "editor: drag pieces from the palette or the board, [right click] removes  \
+             [tab] side to move  [1]-[4] castling KQkq  [c] clear  [i] initial position  \
+             [x] flip  [enter] start game from here  [esc] back"
+        );
+        self.update_batch();
+        self.update_editor_text();
+    }
+
+    /// leaves the editor, the position becomes a new game if `start`
+    fn leave_editor(&mut self, start: bool) {
+        let editor = match self.editor.as_ref() {
+            Some(editor) => editor,
+            None => return,
+        };
+        if start {
+            if let Err(err) = self.board.validate(self.turn) {
+                log::warn!("invalid position: {err}");
+                return;
+            }
+            // the rights become part of the game, not just its fen tag
+            let board = editor.with_castling(&self.board);
+            let mut tree = GameTree::new(board.clone(), self.turn);
+            tree.set_tag("SetUp", "1");
+            tree.set_tag("FEN", board.to_fen(self.turn));
+            self.editor = None;
+            self.set_game(tree, &[]);
+        } else {
+            let (board, side) = editor.previous.clone();
+            self.editor = None;
+            self.set_board(board);
+            self.turn = side;
+        }
+
+        for &idx in self.palette_quads.iter() {
+            self.tex_batcher.get_mut(idx).col.w = 0.0;
+        }
+        self.tex_batcher.get_mut(self.hand_quad).col.w = 0.0;
+        self.update_batch();
+        self.update_menu_text();
+        if start {
+            self.start_game();
+        }
+    }
+
+    fn editor_key(&mut self, key: VirtualKeyCode) {
+        let editor = match self.editor.as_mut() {
+            Some(editor) => editor,
+            None => return,
+        };
+        match key {
+            VirtualKeyCode::Tab => self.turn = self.turn.other(),
+            VirtualKeyCode::X => return self.set_flipped(!self.flipped),
+            VirtualKeyCode::Key1 => editor.toggle_castling(0),
+            VirtualKeyCode::Key2 => editor.toggle_castling(1),
+            VirtualKeyCode::Key3 => editor.toggle_castling(2),
+            VirtualKeyCode::Key4 => editor.toggle_castling(3),
+            VirtualKeyCode::C => self.set_board(Board::parse_fen("8/8/8/8/8/8/8/8").unwrap()),
+            VirtualKeyCode::I => {
+                self.set_board(Board::starting());
+                self.turn = Side::White;
+            }
+            VirtualKeyCode::Return => return self.leave_editor(true),
+            VirtualKeyCode::Escape => return self.leave_editor(false),
+            _ => return,
+        }
+        self.update_editor_text();
+    }
+
+    /// picks up a palette piece or a piece on the board
+    fn editor_press(&mut self) {
+        let picked = match palette_hit(self.mouse) {
+            Some((side, piece)) => Some((side, piece, None)),
+            None => self.cursor.and_then(|(pos, _)| {
+                let (side, piece) = self.board.get_piece(&pos)?;
+                self.set_square(pos, None);
+                Some((side, piece, Some(pos)))
+            }),
+        };
+        let (side, piece, from) = match picked {
+            Some(picked) => picked,
+            None => return,
+        };
+        if let Some(editor) = self.editor.as_mut() {
+            editor.dragging = Some((side, piece, from));
+        }
+        self.update_hand();
+        self.update_editor_text();
+    }
+
+    /// drops the dragged piece, off the board it is gone
+    fn editor_release(&mut self) {
+        let dragging = self
+            .editor
+            .as_mut()
+            .and_then(|editor| editor.dragging.take());
+        let (side, piece, _) = match dragging {
+            Some(dragging) => dragging,
+            None => return,
+        };
+        if let Some((pos, _)) = self.cursor {
+            self.set_square(pos, Some((side, piece)));
+        }
+        self.update_hand();
+        self.update_editor_text();
+    }
+
+    /// the held piece follows the mouse
+    fn update_hand(&mut self) {
+        let held = match (self.editor.as_ref(), self.moving, self.dragging) {
+            (Some(editor), ..) => editor.dragging.map(|(side, piece, _)| (side, piece)),
+            (None, Some((_, side, piece)), Some(_)) => Some((side, piece)),
+            _ => None,
+        };
+        let tex = held.map(|(side, piece)| self.piece_tex(side, piece));
+        let quad = self.tex_batcher.get_mut(self.hand_quad);
+        match tex {
+            Some(tex) => {
+                quad.pos = self.mouse - 0.125;
+                quad.col = Vec4::new(1.0, 1.0, 1.0, 1.0);
+                quad.tex = tex;
+            }
+            None => quad.col.w = 0.0,
+        }
+    }
+
+    /// picks up a piece of the side to move or drops the picked one on a
+    /// square it can go to, either way a held piece can be dragged
+    fn game_press(&mut self) {
+        let pos = match self.cursor {
+            Some((pos, _)) => pos,
+            None => return,
+        };
+        if let Some((from, side, _)) = self.moving {
+            if pos == from {
+                // released in place it is put down
+                self.dragging = Some(true);
+                self.update_batch();
+                self.update_hand();
+                return;
+            }
+            if let Some(mv) = self.human_move(from, pos, side) {
+                log::debug!("drop {pos} from {from}");
+                self.moving = None;
+                self.play_move(mv);
+                return;
+            }
+        }
+
+        match self.board.get_piece(&pos) {
+            Some((side, piece)) if side == self.turn => {
+                log::debug!("pick {pos}");
+                self.moving = Some((pos, side, piece));
+                self.dragging = Some(false);
+                self.update_batch();
+                self.update_hand();
+            }
+            Some(_) if self.moving.is_none() => log::debug!("wrong player"),
+            _ => log::debug!("invalid move"),
+        }
+    }
+
+    /// puts the held piece down, where it cannot go it snaps back
+    fn game_release(&mut self) {
+        let picked_before = match self.dragging.take() {
+            Some(picked_before) => picked_before,
+            None => return,
+        };
+        let (from, side, _) = match self.moving {
+            Some(moving) => moving,
+            None => return self.update_hand(),
+        };
+
+        let to = self.cursor.map(|(pos, _)| pos);
+        let mv = to.and_then(|to| self.human_move(from, to, side));
+        match (to, mv) {
+            // a click, the piece stays picked for click to move
+            (Some(to), _) if to == from => {
+                if picked_before {
+                    log::debug!("move cancelled");
+                    self.moving = None;
+                }
+            }
+            (Some(to), Some(mv)) => {
+                log::debug!("drop {to} from {from}");
+                self.moving = None;
+                self.play_move(mv);
+            }
+            _ => {
+                log::debug!("invalid move");
+                self.moving = None;
+            }
+        }
+        self.update_batch();
+        self.update_hand();
+    }
+
+    /// replaces what stands on `pos`
+    fn set_square(&mut self, pos: BoardPos, piece: Option<(Side, Piece)>) {
+        self.board.remove_piece(&pos);
+        // update_batch only shows occupied squares
+        self.tex_batcher
+            .get_mut(self.piece_quads[pos.to_usize()])
+            .col
+            .w = 0.0;
+        if let Some((side, piece)) = piece {
+            self.board.set_piece(side, piece, pos);
+        }
+        self.update_batch();
+    }
+
+    /// replaces the whole board
+    fn set_board(&mut self, board: Board) {
+        for &idx in self.piece_quads.iter() {
+            self.tex_batcher.get_mut(idx).col.w = 0.0;
+        }
+        self.board = board;
+        self.update_batch();
+        self.update_tablebase_text();
+    }
+
+    /// side to move and castling or what is wrong with the position
+    fn update_editor_text(&mut self) {
+        let editor = match self.editor.as_ref() {
+            Some(editor) => editor,
+            None => return,
+        };
+        let (status, col) = match self.board.validate(self.turn) {
+            Ok(()) => (
+                format!(
+                    "{} to move  castling {}",
+                    side_name(self.turn),
+                    editor.castling_field(&self.board)
+                ),
+                Vec4::new(0.8, 0.8, 0.8, 1.0),
+            ),
+            Err(err) => (err.to_string(), Vec4::new(0.9, 0.4, 0.4, 1.0)),
+        };
+        let lines = [
+            (status, col),
+            (
+                "[tab] [1]-[4] [c] [i] [enter] [esc]".to_string(),
+                Vec4::new(0.8, 0.8, 0.8, 1.0),
+            ),
+        ];
+        for (i, (text, (line, col))) in self.menu_text.iter_mut().zip(lines).enumerate() {
+            text.set(
+                &mut self.text_batcher,
+                &line,
+                Vec2::new(-1.0, 1.0 + TEXT_SIZE * (0.25 + 1.25 * i as f32)),
+                TEXT_SIZE,
+                col,
+            );
+        }
+    }
+
+    fn open_input(&mut self) {
+        self.input = Some(TextInput::new());
+        self.tex_batcher.get_mut(self.hand_quad).col.w = 0.0;
+        self.text_batcher.get_mut(self.input_quad).col.w = 0.85;
+        log::info!("type or paste a fen or a pgn move list, [enter] loads it, [esc] cancels");
+        self.update_input_text();
+    }
+
+    fn close_input(&mut self) {
+        self.input = None;
+        self.text_batcher.get_mut(self.input_quad).col.w = 0.0;
+        for text in self.input_text.iter_mut() {
+            text.clear(&mut self.text_batcher);
+        }
+    }
+
+    fn input_key(&mut self, key: VirtualKeyCode) {
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => return,
+        };
+        match key {
+            VirtualKeyCode::Back => input.pop(),
+            VirtualKeyCode::Escape => return self.close_input(),
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => match input.parse() {
+                Ok(tree) => {
+                    self.close_input();
+                    let moves = vec![0; tree.main_line(&[]).len()];
+                    log::info!("loaded {} plies", moves.len());
+                    self.set_game(tree, &moves);
+                    return;
+                }
+                Err(err) => input.error = Some(err),
+            },
+            _ => return,
+        }
+        self.update_input_text();
+    }
+
+    fn update_input_text(&mut self) {
+        let input = match self.input.as_ref() {
+            Some(input) => input,
+            None => return,
+        };
+        let error = input
+            .error
+            .as_ref()
+            .map(|err| err.to_string())
+            .unwrap_or_default();
+        let lines = [
+            (
+                "fen or pgn  [enter] load  [esc] cancel".to_string(),
+                Vec4::new(0.5, 0.5, 0.5, 1.0),
+            ),
+            (input.visible(), Vec4::new(1.0, 1.0, 1.0, 1.0)),
+            (error, Vec4::new(0.9, 0.4, 0.4, 1.0)),
+        ];
+        for (i, (text, (line, col))) in self.input_text.iter_mut().zip(lines).enumerate() {
+            text.set(
+                &mut self.text_batcher,
+                &line,
+                Vec2::new(-0.96, -0.16 + TEXT_SIZE * 1.5 * i as f32),
+                TEXT_SIZE * 0.5,
+                col,
+            );
+        }
+    }
+
+    /// the tree with the tags of this game
+    fn tagged_tree(&self) -> GameTree {
+        let mut tree = self.tree.clone();
+        if tree.tag("Event").is_none() {
+            tree.set_tag("Event", "casual game");
+        }
+        if self.started {
+            tree.set_tag("White", self.players[0].to_string());
+            tree.set_tag("Black", self.players[1].to_string());
+        }
+        tree.result = self.result.unwrap_or_default();
+        tree.set_tag("Result", tree.result.to_string());
+        // a fen from the editor keeps its castling rights
+        if (tree.board != Board::starting() || tree.side != Side::White)
+            && tree.tag("FEN").is_none()
+        {
+            tree.set_tag("SetUp", "1");
+            tree.set_tag("FEN", tree.board.to_fen(tree.side));
+        }
+        tree
+    }
+
+    /// the game with its variations as pgn
+    fn save_game(&self) {
+        self.save("game", "pgn", &self.tagged_tree().to_string());
+    }
+
+    /// the current position as fen
+    fn save_position(&self) {
+        self.save(
+            "position",
+            "fen",
+            &format!("{}\n", self.board.to_fen(self.turn)),
+        );
+    }
+
+    /// writes a new file in the games directory
+    fn save(&self, name: &str, extension: &str, contents: &str) {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = self.games_dir.join(format!("{name}-{secs}.{extension}"));
+        match fs::create_dir_all(&self.games_dir).and_then(|_| fs::write(&path, contents)) {
+            Ok(()) => log::info!("saved {}", path.display()),
+            Err(err) => log::error!("could not save {}: {err}", path.display()),
+        }
+    }
+
+    /// loads the most recently saved game or position
+    fn load_newest(&mut self) {
+        match newest_save(&self.games_dir) {
+            Ok(Some(path)) => self.load(&path),
+            Ok(None) => log::info!("nothing saved in {}", self.games_dir.display()),
+            Err(err) => log::error!("could not read {}: {err}", self.games_dir.display()),
+        }
+    }
+
+    /// a `.fen` position or the first game of a pgn file, the autosave
+    /// continues where it left off
+    fn load(&mut self, path: &Path) {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                log::error!("could not read {}: {err}", path.display());
+                return;
+            }
+        };
+
+        let tree = if path.extension().is_some_and(|ext| ext == "fen") {
+            match Board::from_fen(text.trim()) {
+                Ok((board, side)) => GameTree::new(board, side),
+                Err(err) => {
+                    log::error!("invalid fen in {}: {err}", path.display());
+                    return;
+                }
+            }
+        } else {
+            match GameTree::parse(&text) {
+                Some(tree) => tree,
+                None => {
+                    log::error!("no game in {}", path.display());
+                    return;
+                }
+            }
+        };
+
+        // the end of the main line unless the autosave says otherwise
+        let moves = match tree.tag(CURRENT_PATH) {
+            Some(path) => path
+                .split_whitespace()
+                .filter_map(|i| i.parse().ok())
+                .collect(),
+            None => vec![0; tree.main_line(&[]).len()],
+        };
+        log::info!("loaded {}", path.display());
+        self.set_game(tree, &moves);
+    }
 
-struct App {
-    color_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
-    tex_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
-    circle_batcher: BatchRenderer<DefaultVertex, QuadMesh>,
-    color_program: Program,
-    tex_program: Program,
-    circle_program: Program,
-    texture: TextureAtlasMap<(Side, Piece)>,
+    /// replaces the game and goes to the position at `path`
+    fn set_game(&mut self, mut tree: GameTree, path: &[usize]) {
+        self.stop_thinking();
+        self.moving = None;
+        for &idx in self.piece_quads.iter() {
+            self.tex_batcher.get_mut(idx).col.w = 0.0;
+        }
 
-    turn: Side,
+        tree.tags.retain(|(name, _)| name != CURRENT_PATH);
+        self.board = tree.board.clone();
+        self.turn = tree.side;
+        self.history.clear();
+        self.path.clear();
+        self.tree = tree;
+        for &i in path {
+            match self
+                .tree
+                .children(&self.path)
+                .and_then(|children| children.get(i))
+            {
+                Some(node) => self.make_move(node.mv),
+                None => break,
+            }
+        }
 
-    circle_quads: [Idx; 64],
-    piece_quads: [Idx; 64],
-    hand_quad: Idx,
-    board: Board,
+        self.result = Some(self.tree.result).filter(|&result| result != GameResult::Unknown);
+        match self.result {
+            Some(result) => {
+                let reason = self.tree.tag("Termination").unwrap_or_default();
+                self.result_text.set(
+                    &mut self.text_batcher,
+                    &format!("{result} {reason}"),
+                    Vec2::new(-1.0, 1.0 + TEXT_SIZE * 1.5),
+                    TEXT_SIZE,
+                    Vec4::new(1.0, 1.0, 1.0, 1.0),
+                );
+            }
+            None => self.result_text.clear(&mut self.text_batcher),
+        }
 
-    cursor: Option<(BoardPos, Vec2)>,
-    moving: Option<(BoardPos, Side, Piece)>,
-}
+        // loaded games are reviewed first
+        if self.started {
+            self.clock = self.time_control.clone().map(Clock::new);
+            self.start_replay();
+        }
+        self.after_step();
+    }
 
-//
+    /// keeps an unfinished game for the next session and forgets a
+    /// finished one
+    fn autosave(&self) {
+        let path = self.games_dir.join(AUTOSAVE);
+        if self.result.is_some() || self.tree.children.is_empty() {
+            if let Err(err) = fs::remove_file(&path) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    log::error!("could not remove {}: {err}", path.display());
+                }
+            }
+            return;
+        }
 
-impl App {
-    fn update_batch(&mut self) {
-        // log::debug!("board = {:?}", self.board);
-        for (side, piece, pos) in self.board.iter() {
-            let i = pos.to_usize();
-            let idx = self.piece_quads[i];
+        let mut tree = self.tagged_tree();
+        let current = self.path.iter().map(usize::to_string).collect::<Vec<_>>();
+        tree.set_tag(CURRENT_PATH, current.join(" "));
+        match fs::create_dir_all(&self.games_dir).and_then(|_| fs::write(&path, tree.to_string())) {
+            Ok(()) => log::info!("saved the game to {}", path.display()),
+            Err(err) => log::error!("could not save {}: {err}", path.display()),
+        }
+    }
 
-            // log::debug!("{side:?} {piece:?} at {pos}");
+    /// flag fall loses, or draws when the opponent cannot mate
+    fn check_flag(&mut self) {
+        let side = match self.clock.as_ref().and_then(Clock::flagged) {
+            Some(side) => side,
+            None => return,
+        };
+        let result = match side {
+            _ if !can_mate(&self.board, side.other()) => GameResult::Draw,
+            Side::White => GameResult::BlackWins,
+            Side::Black => GameResult::WhiteWins,
+        };
+        self.end_game(result, &format!("{} flagged", side_name(side)));
+    }
 
-            let quad = self.tex_batcher.get_mut(idx);
-            quad.size = Vec2::new(0.25, 0.25);
-            quad.pos = Vec2::new(
-                pos.file as f32 * 0.25 - 1.25,
-                -(pos.rank as f32 * 0.25 - 1.0),
-            );
-            quad.col = Vec4::new(1.0, 1.0, 1.0, 1.0);
-            quad.tex = *self.texture.get(&(side, piece)).unwrap();
-            quad.tex.top_left.y = 1.0 - quad.tex.top_left.y;
-            quad.tex.bottom_right.y = 1.0 - quad.tex.bottom_right.y;
+    /// checkmate loses and stalemate draws for the side to move
+    fn check_mate(&mut self) {
+        let side = self.turn;
+        if !self.board.legal_moves(side).is_empty() {
+            return;
         }
+        let (result, reason) = match side {
+            _ if !self.board.in_check(side) => (GameResult::Draw, "stalemate".to_string()),
+            Side::White => (GameResult::BlackWins, format!("{} mated", side_name(side))),
+            Side::Black => (GameResult::WhiteWins, format!("{} mated", side_name(side))),
+        };
+        self.end_game(result, &reason);
+    }
 
-        if let Some((pos, side, piece)) = self.moving.as_ref() {
-            // piece picked up
-            let quad = self.tex_batcher.get_mut(self.piece_quads[pos.to_usize()]);
-            quad.pos -= 0.02;
-            quad.size += 0.04;
+    /// stops the clocks and every engine
+    fn end_game(&mut self, result: GameResult, reason: &str) {
+        log::info!("{result} ({reason})");
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop();
+        }
+        self.stop_thinking();
+        self.result_text.set(
+            &mut self.text_batcher,
+            &format!("{result} {reason}"),
+            Vec2::new(-1.0, 1.0 + TEXT_SIZE * 1.5),
+            TEXT_SIZE,
+            Vec4::new(1.0, 1.0, 1.0, 1.0),
+        );
+        self.result = Some(result);
+        self.start_replay();
+    }
 
-            // piece valid moves
-            for piece in BoardPos::iter() {
-                let idx = self.circle_quads[piece.to_usize()];
-                if (self.circle_batcher.get(idx).col.w).abs() >= std::f32::EPSILON {
-                    self.circle_batcher.get_mut(idx).col.w = 0.0;
-                }
-            }
-            for piece in piece.moves(&self.board, *pos, *side) {
-                if self.board.get_piece(&piece).is_some() {
-                    // if it is a capture
-                    // draw a frame around it
-                    let idx = self.circle_quads[piece.to_usize()];
-                    if self.circle_batcher.get(idx).col.w >= std::f32::EPSILON - 1.0 {
-                        self.circle_batcher.get_mut(idx).col.w = -1.0;
-                    }
-                } else {
-                    // if it is just a move
-                    // draw a dot in it
-                    let idx = self.circle_quads[piece.to_usize()];
-                    if self.circle_batcher.get(idx).col.w <= 1.0 - std::f32::EPSILON {
-                        self.circle_batcher.get_mut(idx).col.w = 1.0;
-                    }
-                }
-            }
-        } else {
-            for piece in BoardPos::iter() {
-                let idx = self.circle_quads[piece.to_usize()];
-                if (self.circle_batcher.get(idx).col.w).abs() >= std::f32::EPSILON {
-                    self.circle_batcher.get_mut(idx).col.w = 0.0;
+    /// black's clock above and white's below the right edge of the board
+    fn update_clock_text(&mut self) {
+        for side in [Side::White, Side::Black] {
+            let (time, col) = match self.clock.as_ref() {
+                Some(clock) => {
+                    let time = clock.remaining(side);
+                    let col = if time.is_zero() {
+                        Vec4::new(0.9, 0.2, 0.2, 1.0)
+                    } else if clock.is_running(side) {
+                        Vec4::new(1.0, 1.0, 1.0, 1.0)
+                    } else {
+                        Vec4::new(0.5, 0.5, 0.5, 1.0)
+                    };
+                    (format_time(time), col)
                 }
+                // the time the game will start with
+                None => match self.time_control.as_ref().filter(|_| !self.started) {
+                    Some(control) => (
+                        format_time(control.periods[0].time),
+                        Vec4::new(0.5, 0.5, 0.5, 1.0),
+                    ),
+                    None => (String::new(), Vec4::ZERO),
+                },
+            };
+
+            let i = side as usize;
+            if self.clock_shown[i] == (time.clone(), col) {
+                continue;
             }
+            let y = if side == self.bottom() {
+                1.0 - TEXT_SIZE
+            } else {
+                -1.0
+            };
+            self.clock_text[i].set(
+                &mut self.text_batcher,
+                &time,
+                Vec2::new(1.05, y),
+                TEXT_SIZE,
+                col,
+            );
+            self.clock_shown[i] = (time, col);
+        }
+    }
+
+    fn update_menu_text(&mut self) {
+        let clock = match self.time_control.as_ref() {
+            Some(control) => control.to_string(),
+            None => "off".to_string(),
+        };
+        let lines = [
+            format!(
+                "white {}  black {}  clock {clock}",
+                self.players[0], self.players[1]
+            ),
+            "[w] [b] [t] change  [l] [v] load  [e] edit  [enter] start".to_string(),
+        ];
+        for (i, (text, line)) in self.menu_text.iter_mut().zip(lines).enumerate() {
+            text.set(
+                &mut self.text_batcher,
+                &line,
+                Vec2::new(-1.0, 1.0 + TEXT_SIZE * (0.25 + 1.25 * i as f32)),
+                TEXT_SIZE,
+                Vec4::new(0.8, 0.8, 0.8, 1.0),
+            );
         }
     }
 
+    fn update_tablebase_text(&mut self) {
+        let wdl = self
+            .tablebase
+            .as_ref()
+            .and_then(|tablebase| tablebase.probe_wdl(&self.board, self.turn));
+        let (text, col) = match wdl {
+            Some(Wdl::Win) => (
+                format!("tablebase win for {}", side_name(self.turn)),
+                Vec4::new(0.4, 0.9, 0.4, 1.0),
+            ),
+            Some(Wdl::Draw) => ("tablebase draw".to_string(), Vec4::new(0.8, 0.8, 0.8, 1.0)),
+            Some(Wdl::Loss) => (
+                format!("tablebase loss for {}", side_name(self.turn)),
+                Vec4::new(0.9, 0.4, 0.4, 1.0),
+            ),
+            None => {
+                self.tablebase_text.clear(&mut self.text_batcher);
+                return;
+            }
+        };
+        self.tablebase_text.set(
+            &mut self.text_batcher,
+            &text,
+            Vec2::new(-1.0, -1.0 - TEXT_SIZE * 1.5),
+            TEXT_SIZE,
+            col,
+        );
+    }
+
     pub fn circle_program<F>(facade: &F) -> Program
     where
         F: Facade,
@@ -203,6 +1650,15 @@ impl Runnable<Engine> for App {
         let mut color_batcher = BatchRenderer::new(&gl.engine);
         let mut tex_batcher = BatchRenderer::new(&gl.engine);
         let mut circle_batcher = BatchRenderer::new(&gl.engine);
+        let mut text_batcher = BatchRenderer::new(&gl.engine);
+
+        // pushed first to stay under every text
+        let input_quad = text_batcher.push_with(QuadMesh {
+            pos: Vec2::new(-1.0, -0.2),
+            size: Vec2::new(2.0, 0.4),
+            col: Vec4::new(0.0, 0.0, 0.0, 0.0),
+            tex: TexturePosition::default(),
+        });
 
         (0..64)
             .map(|i| {
@@ -260,6 +1716,19 @@ impl Runnable<Engine> for App {
             .try_into()
             .unwrap();
 
+        let palette_quads = (0..12)
+            .map(|_| {
+                tex_batcher.push_with(QuadMesh {
+                    pos: Vec2::new(0.0, 0.0),
+                    size: Vec2::new(PALETTE_SIZE, PALETTE_SIZE),
+                    col: Vec4::new(0.0, 0.0, 0.0, 0.0),
+                    tex: TexturePosition::default(),
+                })
+            })
+            .collect::<Vec<Idx>>()
+            .try_into()
+            .unwrap();
+
         let hand_quad = tex_batcher.push_with(QuadMesh {
             pos: Vec2::new(0.0, 0.0),
             size: Vec2::new(0.25, 0.25),
@@ -270,10 +1739,42 @@ impl Runnable<Engine> for App {
         let board = Board::starting();
         log::debug!("{board:?}");
 
+        let book = match Book::open(BOOK_PATH) {
+            Ok(book) => {
+                log::info!("loaded {} book entries from {BOOK_PATH}", book.len());
+                Some(book)
+            }
+            Err(err) => {
+                log::info!("no opening book: {err}");
+                None
+            }
+        };
+
+        let tablebase = match Tablebase::open(SYZYGY_PATH) {
+            Ok(tablebase) => {
+                log::info!(
+                    "loaded syzygy tables up to {} pieces from {SYZYGY_PATH}",
+                    tablebase.max_pieces()
+                );
+                Some(Arc::new(tablebase))
+            }
+            Err(err) => {
+                log::info!("no syzygy tables: {err}");
+                None
+            }
+        };
+        let thinkers = [(); 2].map(|_| {
+            let mut thinker = Thinker::new(SearchOptions::default());
+            thinker.search().set_tablebase(tablebase.clone());
+            thinker
+        });
+        let args = PlayerArgs::parse(std::env::args().skip(1));
+
         let mut res = Self {
             color_batcher,
             tex_batcher,
             circle_batcher,
+            text_batcher,
             color_program,
             tex_program,
             circle_program,
@@ -283,44 +1784,99 @@ impl Runnable<Engine> for App {
 
             circle_quads,
             piece_quads,
+            palette_quads,
             hand_quad,
             board,
 
             cursor: None,
+            mouse: Vec2::ZERO,
             moving: None,
+            dragging: None,
+            flipped: false,
+
+            history: Vec::new(),
+            tree: GameTree::new(Board::starting(), Side::White),
+            path: Vec::new(),
+            // right of the board between the clocks
+            move_list: MoveList::new(
+                Vec2::new(1.05, -1.0 + TEXT_SIZE * 1.5),
+                2.0 - TEXT_SIZE * 3.0,
+            ),
+
+            players: args.players,
+            engines: args.engines,
+            thinkers,
+            externals: [None, None],
+            analyser_path: args.analyser,
+            analyser: None,
+            analysed: None,
+            analysis: String::new(),
+            analysis_text: Text::new(),
+            ponder: false,
+            book,
+            tablebase,
+            tablebase_text: Text::new(),
+
+            time_control: args.time_control,
+            clock: None,
+            clock_text: [Text::new(), Text::new()],
+            clock_shown: Default::default(),
+
+            started: false,
+            menu_text: [Text::new(), Text::new()],
+            result: None,
+            result_text: Text::new(),
+
+            replaying: false,
+            autoplay: None,
+            replay_interval: args.replay_interval,
+            replay_text: Text::new(),
+
+            games_dir: args.games_dir,
+
+            editor: None,
+
+            input: None,
+            input_quad,
+            input_text: [Text::new(), Text::new(), Text::new()],
         };
 
         res.update_batch();
+        res.update_tablebase_text();
+        res.update_menu_text();
+
+        let autosave = res.games_dir.join(AUTOSAVE);
+        if autosave.exists() {
+            res.load(&autosave);
+        }
 
         res
     }
 
     fn update(&mut self, _: &mut GameLoop<Engine>) {
-        if self.turn == Side::Black {
-            let mut rng = rand::thread_rng();
-            let (to_move, moves) = self
-                .board
-                .iter()
-                .filter(|(side, _, _)| *side == Side::Black) // filter AI pieces
-                .map(|(side, piece, pos)| {
-                    (pos, piece.moves(&self.board, pos, side).collect::<Vec<_>>())
-                })
-                .filter(|(_, moves)| !moves.is_empty()) // filter pieces that can move
-                .choose(&mut rng)
-                .unwrap_or_else(|| {
-                    log::info!("AI lost");
-                    exit(0);
-                });
-            let move_to = moves.choose(&mut rng).unwrap();
+        self.update_clock_text();
+        if !self.started {
+            return;
+        }
+
+        self.update_analysis();
+        if self.replaying {
+            self.update_autoplay();
+            return;
+        }
+        self.check_flag();
+        if self.result.is_none() {
+            self.check_mate();
+        }
+        if self.result.is_some() {
+            return;
+        }
 
-            let piece_quad = self
-                .tex_batcher
-                .get_mut(self.piece_quads[to_move.to_usize()]);
-            piece_quad.col.w = 0.0;
-            let (side, piece) = self.board.remove_piece(&to_move).unwrap();
-            self.board.set_piece(side, piece, *move_to);
-            self.update_batch();
-            self.turn = Side::White;
+        let side = self.turn;
+        match self.players[side as usize].clone() {
+            Player::Human => {}
+            Player::Engine(difficulty) => self.update_engine(side, difficulty),
+            Player::External(_) => self.update_external(side),
         }
     }
 
@@ -330,9 +1886,67 @@ impl Runnable<Engine> for App {
             ..
         }) = event
         {
+            self.autosave();
             gl.stop();
         }
 
+        if self.input.is_some() {
+            match event {
+                // winit 0.26, the version main_game_loop is built on, has no
+                // `WindowEvent::Ime` yet, committed ime text arrives here one
+                // character at a time
+                Event::WinitEvent(WinitEvent::WindowEvent {
+                    event: WindowEvent::ReceivedCharacter(c),
+                    ..
+                }) => {
+                    if let Some(input) = self.input.as_mut() {
+                        input.push(*c);
+                    }
+                    self.update_input_text();
+                    return;
+                }
+                Event::WinitEvent(WinitEvent::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state,
+                                    virtual_keycode,
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                }) => {
+                    if let (ElementState::Pressed, Some(key)) = (state, virtual_keycode) {
+                        self.input_key(*key);
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        // opened on release, the `v` itself is not typed into it
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Released,
+                            virtual_keycode: Some(VirtualKeyCode::V),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        }) = event
+        {
+            if self.editor.is_none() {
+                self.open_input();
+            }
+        }
+
         if let Event::WinitEvent(WinitEvent::WindowEvent {
             event: WindowEvent::CursorLeft { .. },
             ..
@@ -341,6 +1955,64 @@ impl Runnable<Engine> for App {
             self.cursor = None;
         }
 
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event: WindowEvent::MouseWheel { delta, .. },
+            ..
+        }) = event
+        {
+            let rows = match delta {
+                MouseScrollDelta::LineDelta(_, y) => -y.round() as i32,
+                MouseScrollDelta::PixelDelta(pos) => -(pos.y / 20.0).round() as i32,
+            };
+            self.move_list.scroll(&mut self.text_batcher, rows);
+        }
+
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::P),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        }) = event
+        {
+            self.ponder = !self.ponder;
+            log::info!("pondering {}", if self.ponder { "on" } else { "off" });
+            for thinker in self.thinkers.iter_mut() {
+                if !self.ponder && thinker.is_pondering() {
+                    thinker.stop();
+                }
+            }
+        }
+
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        }) = event
+        {
+            if self.editor.is_some() {
+                self.editor_key(*key);
+            } else if !self.started {
+                self.menu_key(*key);
+            } else {
+                self.game_key(*key);
+            }
+        }
+
         if let Event::WinitEvent(WinitEvent::WindowEvent {
             event: WindowEvent::CursorMoved { position, .. },
             ..
@@ -353,15 +2025,19 @@ impl Runnable<Engine> for App {
             // map to world pos
             let x = x / gl.size.0 * gl.aspect * 2.4 - gl.aspect * 1.2;
             let y = -(y / gl.size.1 * 2.4 - 1.2);
+            self.mouse = Vec2::new(x, -y);
+            self.update_hand();
 
             // map to board pos
             let x = ((x + 1.0) * 4.0).floor() as i32 + 1;
             let y = ((y + 1.0) * 4.0).floor() as i32 + 1;
+            let (x, y) = if self.flipped { (9 - x, 9 - y) } else { (x, y) };
 
             let pos = match BoardPos::new(x, y) {
                 Some(ok) => ok,
                 None => {
                     // log::debug!("BoardPos out of bounds");
+                    self.cursor = None;
                     return;
                 }
             };
@@ -374,57 +2050,66 @@ impl Runnable<Engine> for App {
             event:
                 WindowEvent::MouseInput {
                     button: MouseButton::Left,
-                    state: ElementState::Pressed,
+                    state: ElementState::Released,
                     ..
                 },
             ..
         }) = event
         {
-            if self.turn == Side::Black {
-                log::debug!("It is AI:s turn");
+            if self.editor.is_some() {
+                self.editor_release();
+            } else {
+                self.game_release();
             }
-            if let Some((pos, _)) = self.cursor.as_ref() {
-                if let Some((old_pos, side, piece)) = self.moving.take() {
-                    if old_pos != *pos
-                        && !piece
-                            .moves(&self.board, old_pos, side)
-                            .any(|possible_pos| &possible_pos == pos)
-                    {
-                        log::debug!("invalid move");
-                        self.moving = Some((old_pos, side, piece));
-                        return;
-                    }
-
-                    if old_pos == *pos {
-                        log::debug!("move cancelled");
-                        self.board.set_piece(side, piece, *pos);
-                        self.update_batch();
-                        return;
-                    }
-
-                    self.turn = self.turn.other();
-                    log::debug!("drop {pos} from {old_pos}");
-                    let piece_quad = self
-                        .tex_batcher
-                        .get_mut(self.piece_quads[old_pos.to_usize()]);
-                    piece_quad.col.w = 0.0;
-                    self.board.remove_piece(&old_pos);
-                    self.board.set_piece(side, piece, *pos);
-                    self.update_batch();
-                } else if let Some((side, piece)) = self.board.get_piece(pos) {
-                    if side != self.turn {
-                        log::debug!("wrong player");
-                        return;
-                    }
+        }
 
-                    log::debug!("pick {pos}");
-                    let piece_quad = self.tex_batcher.get_mut(self.piece_quads[pos.to_usize()]);
-                    piece_quad.col.w = 0.0;
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    button: MouseButton::Right,
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        }) = event
+        {
+            if let (Some(_), Some((pos, _))) = (self.editor.as_ref(), self.cursor) {
+                self.set_square(pos, None);
+                self.update_editor_text();
+            }
+        }
 
-                    self.moving = Some((*pos, side, piece));
-                    self.update_batch();
-                }
+        if let Event::WinitEvent(WinitEvent::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    button: MouseButton::Left,
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        }) = event
+        {
+            if self.editor.is_some() {
+                self.editor_press();
+                return;
+            }
+            if !self.started {
+                log::debug!("pick the players first");
+                return;
+            }
+            if let Some(plies) = self.move_list.hit(self.mouse) {
+                self.jump(plies);
+                return;
             }
+            if self.replaying {
+                log::debug!("replaying, [r] plays from here");
+                return;
+            }
+            if !self.players[self.turn as usize].is_human() {
+                log::debug!("It is AI:s turn");
+                return;
+            }
+            self.game_press();
         }
     }
 
@@ -486,14 +2171,111 @@ impl Runnable<Engine> for App {
                 },
             )
             .unwrap();
+
+        let ubo = uniform! {
+            mat: Mat4::orthographic_rh_gl(-gl.aspect * 1.2, gl.aspect * 1.2, -1.2, 1.2, 0.0, 100.0).to_cols_array_2d()
+        };
+
+        let (vbo, ibo) = self.text_batcher.draw(&gl.engine);
+        frame
+            .draw(
+                vbo,
+                ibo,
+                &self.color_program,
+                &ubo,
+                &DrawParameters {
+                    blend: Blend::alpha_blending(),
+                    primitive_restart_index: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
     }
 }
 
 //
 
+/// the last modified `.pgn` or `.fen` file in `dir`
+fn newest_save(dir: &Path) -> io::Result<Option<PathBuf>> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path
+            .extension()
+            .is_some_and(|ext| ext == "pgn" || ext == "fen")
+        {
+            continue;
+        }
+        let modified = fs::metadata(&path)?.modified()?;
+        if newest.as_ref().is_none_or(|&(time, _)| modified > time) {
+            newest = Some((modified, path));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// the time control after `current` in the menu, `None` plays without
+/// clocks
+fn next_time_control(current: &Option<TimeControl>) -> Option<TimeControl> {
+    let controls = TIME_CONTROLS.map(|s| TimeControl::parse(s).expect("valid time control"));
+    let next = match current {
+        None => 0,
+        Some(current) => match controls.iter().position(|control| control == current) {
+            Some(i) => i + 1,
+            None => 0,
+        },
+    };
+    controls.get(next).cloned()
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::White => "white",
+        Side::Black => "black",
+    }
+}
+
 fn main() {
     env_logger::init();
 
+    // `chess uci` runs the engine without a window
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("uci") => {
+            chess::uci::run().unwrap();
+            return;
+        }
+        Some("book") => {
+            if let Err(err) = chess::book::run(args) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        Some("dtm") => {
+            if let Err(err) = chess::dtm::run(args) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        Some("mate") => {
+            if let Err(err) = chess::mate::run(args) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        Some("dfpn") => {
+            if let Err(err) = chess::dfpn::run(args) {
+                eprintln!("{err}");
+                exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
     WindowBuilder::new()
         .with_title("Chess")
         .build_engine()
@@ -0,0 +1,279 @@
+use crate::{board::Move, pgn::to_san, piece::Side, Board};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead},
+};
+
+//
+
+/// moves the mating side is allowed to try
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Attacks {
+    /// only checks, much faster but misses quiet keys
+    Checks,
+    #[default]
+    All,
+}
+
+/// solution of a mate problem from the mating side's point of view
+///
+/// `mv` is the key move and `replies` every legal defence with the way to
+/// mate after it, no replies means `mv` mates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MateTree {
+    pub mv: Move,
+    pub replies: Vec<(Move, MateTree)>,
+}
+
+struct Solver {
+    board: Board,
+    attacks: Attacks,
+    /// `(hash, moves)` of positions with no mate in that many moves
+    refuted: HashSet<(u64, u32)>,
+    nodes: u64,
+}
+
+//
+
+/// proves a forced mate for `side` in at most `n` moves, `None` refutes it
+///
+/// shorter mates are found first, so the tree is also the fastest mate in
+/// every variation the solver looked at
+pub fn solve_mate(board: &Board, side: Side, n: u32, attacks: Attacks) -> Option<MateTree> {
+    let mut solver = Solver {
+        board: board.clone(),
+        attacks,
+        refuted: HashSet::new(),
+        nodes: 0,
+    };
+    let tree = (1..=n).find_map(|n| solver.attack(side, n));
+    log::debug!("mate search visited {} nodes", solver.nodes);
+    tree
+}
+
+/// `chess mate <n> [checks] [file]`, reads one fen per line from the file
+/// or stdin and prints the solutions
+pub fn run(mut args: impl Iterator<Item = String>) -> io::Result<()> {
+    let usage = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: chess mate <n> [checks] [file]",
+        )
+    };
+    let n = args.next().and_then(|n| n.parse().ok()).ok_or_else(usage)?;
+    let mut attacks = Attacks::All;
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            "checks" => attacks = Attacks::Checks,
+            _ => file = Some(arg),
+        }
+    }
+
+    let input = match file {
+        Some(file) => fs::read_to_string(file)?,
+        None => io::stdin()
+            .lock()
+            .lines()
+            .collect::<io::Result<Vec<_>>>()?
+            .join("\n"),
+    };
+
+    for fen in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (board, side) = match Board::from_fen(fen) {
+            Ok(ok) => ok,
+            Err(err) => {
+                println!("{fen}: {err}");
+                continue;
+            }
+        };
+        match solve_mate(&board, side, n, attacks) {
+            Some(tree) => {
+                println!("{fen}: mate in {}", tree.depth());
+                print!("{}", tree.format(&board, side));
+            }
+            None => println!("{fen}: no mate in {n}"),
+        }
+        println!();
+    }
+    Ok(())
+}
+
+impl MateTree {
+    /// moves of the mating side in the longest variation
+    pub fn depth(&self) -> u32 {
+        1 + self
+            .replies
+            .iter()
+            .map(|(_, tree)| tree.depth())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// the tree in san, one move per line and defences indented
+    pub fn format(&self, board: &Board, side: Side) -> String {
+        let mut out = String::new();
+        self.format_into(&mut out, &mut board.clone(), side, 1, 0);
+        out
+    }
+
+    fn format_into(
+        &self,
+        out: &mut String,
+        board: &mut Board,
+        side: Side,
+        number: u32,
+        indent: usize,
+    ) {
+        let san = to_san(board, side, self.mv);
+        let dots = match side {
+            Side::White => ".",
+            Side::Black => "...",
+        };
+        out.push_str(&format!("{:indent$}{number}{dots} {san}\n", ""));
+
+        let captured = board.play(self.mv);
+        for &(reply, ref tree) in self.replies.iter() {
+            let san = to_san(board, side.other(), reply);
+            let (reply_number, dots) = match side {
+                Side::White => (number, "..."),
+                Side::Black => (number + 1, "."),
+            };
+            out.push_str(&format!("{:indent$}  {reply_number}{dots} {san}\n", ""));
+
+            let reply_captured = board.play(reply);
+            tree.format_into(out, board, side, number + 1, indent + 4);
+            board.unplay(reply, reply_captured);
+        }
+        board.unplay(self.mv, captured);
+    }
+}
+
+impl Solver {
+    /// a mate for `side` in at most `n` moves
+    fn attack(&mut self, side: Side, n: u32) -> Option<MateTree> {
+        let hash = self.board.hash(side);
+        if self.refuted.contains(&(hash, n)) {
+            return None;
+        }
+        self.nodes += 1;
+
+        for mv in self.board.legal_moves(side) {
+            let captured = self.board.play(mv);
+            let check = self.board.in_check(side.other());
+            let tree = if self.attacks == Attacks::Checks && !check {
+                None
+            } else {
+                self.defend(side.other(), n - 1)
+                    .map(|replies| MateTree { mv, replies })
+            };
+            self.board.unplay(mv, captured);
+
+            if tree.is_some() {
+                return tree;
+            }
+        }
+
+        self.refuted.insert((hash, n));
+        None
+    }
+
+    /// every defence of `side` answered with a mate in at most `n` moves,
+    /// `None` if one of them holds
+    fn defend(&mut self, side: Side, n: u32) -> Option<Vec<(Move, MateTree)>> {
+        self.nodes += 1;
+        let moves = self.board.legal_moves(side);
+        if moves.is_empty() {
+            // mate, stalemate does not count
+            return self.board.in_check(side).then(Vec::new);
+        }
+        if n == 0 {
+            return None;
+        }
+
+        let mut replies = Vec::with_capacity(moves.len());
+        for mv in moves {
+            let captured = self.board.play(mv);
+            // the quickest mate after each defence
+            let tree = (1..=n).find_map(|n| self.attack(side.other(), n));
+            self.board.unplay(mv, captured);
+
+            replies.push((mv, tree?));
+        }
+        Some(replies)
+    }
+}
+
+//
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// walks the tree on the board, every defence has to be answered and
+    /// every leaf has to mate
+    pub(crate) fn verify(board: &mut Board, side: Side, tree: &MateTree) {
+        assert!(board.legal_moves(side).contains(&tree.mv));
+        let captured = board.play(tree.mv);
+
+        let defences = board.legal_moves(side.other());
+        if tree.replies.is_empty() {
+            assert!(defences.is_empty() && board.in_check(side.other()));
+        } else {
+            assert_eq!(tree.replies.len(), defences.len());
+            assert!(defences
+                .iter()
+                .all(|mv| tree.replies.iter().any(|(reply, _)| reply == mv)));
+
+            for (reply, tree) in tree.replies.iter() {
+                let reply_captured = board.play(*reply);
+                verify(board, side, tree);
+                board.unplay(*reply, reply_captured);
+            }
+        }
+        board.unplay(tree.mv, captured);
+    }
+
+    fn solve(fen: &str, n: u32, attacks: Attacks) -> Option<(Board, Side, MateTree)> {
+        let (mut board, side) = Board::from_fen(fen).unwrap();
+        let tree = solve_mate(&board, side, n, attacks)?;
+        verify(&mut board, side, &tree);
+        Some((board, side, tree))
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let (board, side, tree) =
+            solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3, Attacks::Checks).unwrap();
+        assert_eq!(tree.depth(), 1);
+        assert_eq!(tree.format(&board, side), "1. Ra8#\n");
+
+        let (board, side, tree) =
+            solve("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 1, Attacks::All).unwrap();
+        assert_eq!(tree.format(&board, side), "1... Ra1#\n");
+    }
+
+    #[test]
+    fn quiet_key() {
+        // 1. Kb6 Kb8 2. Rh8# and friends, every check lets the king out
+        let fen = "k7/8/2K5/8/8/8/8/7R w - - 0 1";
+        assert!(solve(fen, 1, Attacks::All).is_none());
+        assert!(solve(fen, 2, Attacks::Checks).is_none());
+
+        let (board, side, tree) = solve(fen, 2, Attacks::All).unwrap();
+        assert_eq!(tree.depth(), 2);
+        assert!(!tree.replies.is_empty());
+        let text = tree.format(&board, side);
+        assert!(text.starts_with("1. "), "{text}");
+        assert!(text.contains("  1... K"), "{text}");
+        assert!(text.contains("    2. "), "{text}");
+        assert!(text.trim_end().ends_with('#'), "{text}");
+    }
+
+    #[test]
+    fn stalemate_is_not_mate() {
+        // bare kings never mate
+        assert!(solve("k7/8/1K6/8/8/8/8/8 w - - 0 1", 3, Attacks::All).is_none());
+    }
+}
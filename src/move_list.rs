@@ -0,0 +1,234 @@
+use crate::text::Text;
+use chess::piece::Side;
+use srs2dge::{
+    batch::{quad::QuadMesh, BatchRenderer},
+    glam::{Vec2, Vec4},
+    program::DefaultVertex,
+};
+
+//
+
+/// numbered san moves in rows of two, scrolled to keep the current move in
+/// view
+#[derive(Debug)]
+pub struct MoveList {
+    /// top left corner
+    pos: Vec2,
+    /// number of visible rows
+    rows: usize,
+    /// first visible row
+    scroll: usize,
+
+    sans: Vec<String>,
+    /// the side that made the first move
+    first: Side,
+    /// the number of the first full move
+    number: usize,
+    /// moves played, the ones after it can be redone
+    current: usize,
+
+    /// one per visible row
+    numbers: Vec<Text>,
+    /// two per visible row
+    plies: Vec<Text>,
+}
+
+//
+
+/// height of a character
+const SIZE: f32 = 0.03;
+
+const ROW_HEIGHT: f32 = SIZE * 1.4;
+
+/// characters before the white and the black column
+const COLUMNS: [usize; 2] = [4, 11];
+
+//
+
+impl MoveList {
+    /// a list with its top left corner at `pos`, `height` tall
+    pub fn new(pos: Vec2, height: f32) -> Self {
+        let rows = (height / ROW_HEIGHT) as usize;
+        Self {
+            pos,
+            rows,
+            scroll: 0,
+            sans: Vec::new(),
+            first: Side::White,
+            number: 1,
+            current: 0,
+            numbers: (0..rows).map(|_| Text::new()).collect(),
+            plies: (0..rows * 2).map(|_| Text::new()).collect(),
+        }
+    }
+
+    /// replaces the moves, the first made by `first` in full move `number`,
+    /// `current` of them are played
+    pub fn set(
+        &mut self,
+        batcher: &mut BatchRenderer<DefaultVertex, QuadMesh>,
+        sans: Vec<String>,
+        first: Side,
+        number: u32,
+        current: usize,
+    ) {
+        self.sans = sans;
+        self.first = first;
+        self.number = number as usize;
+        self.current = current;
+
+        // follow the current move
+        if let Some(row) = current.checked_sub(1).map(|ply| self.row(ply)) {
+            if row < self.scroll {
+                self.scroll = row;
+            } else if row >= self.scroll + self.rows {
+                self.scroll = row + 1 - self.rows;
+            }
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+        self.draw(batcher);
+    }
+
+    /// scrolls by `rows`, down is positive
+    pub fn scroll(&mut self, batcher: &mut BatchRenderer<DefaultVertex, QuadMesh>, rows: i32) {
+        let scroll = (self.scroll as i64 + rows as i64).clamp(0, self.max_scroll() as i64) as usize;
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.draw(batcher);
+        }
+    }
+
+    /// the number of moves played up to and including the move at `pos`
+    pub fn hit(&self, pos: Vec2) -> Option<usize> {
+        let pos = (pos - self.pos) / SIZE;
+        if pos.y < 0.0 || pos.x < COLUMNS[0] as f32 {
+            return None;
+        }
+        let row = (pos.y * SIZE / ROW_HEIGHT) as usize;
+        if row >= self.rows {
+            return None;
+        }
+        let column = (pos.x >= COLUMNS[1] as f32) as usize;
+        let ply = ((self.scroll + row) * 2 + column).checked_sub(self.offset())?;
+        (ply < self.sans.len()).then_some(ply + 1)
+    }
+
+    fn draw(&mut self, batcher: &mut BatchRenderer<DefaultVertex, QuadMesh>) {
+        let offset = self.offset();
+        for i in 0..self.rows {
+            let row = self.scroll + i;
+            let y = self.pos.y + i as f32 * ROW_HEIGHT;
+
+            let mut any = false;
+            for (column, chars) in COLUMNS.into_iter().enumerate() {
+                let text = &mut self.plies[i * 2 + column];
+                let ply = match (row * 2 + column).checked_sub(offset) {
+                    Some(ply) if ply < self.sans.len() => ply,
+                    _ => {
+                        text.clear(batcher);
+                        continue;
+                    }
+                };
+                let col = if ply + 1 == self.current {
+                    Vec4::new(1.0, 0.85, 0.3, 1.0)
+                } else if ply < self.current {
+                    Vec4::new(0.8, 0.8, 0.8, 1.0)
+                } else {
+                    // undone
+                    Vec4::new(0.4, 0.4, 0.4, 1.0)
+                };
+                let x = self.pos.x + chars as f32 * SIZE;
+                text.set(batcher, &self.sans[ply], Vec2::new(x, y), SIZE, col);
+                any = true;
+            }
+
+            let number = &mut self.numbers[i];
+            if any {
+                let dots = if row == 0 && self.first == Side::Black {
+                    "..."
+                } else {
+                    "."
+                };
+                number.set(
+                    batcher,
+                    &format!("{}{dots}", row + self.number),
+                    Vec2::new(self.pos.x, y),
+                    SIZE,
+                    Vec4::new(0.5, 0.5, 0.5, 1.0),
+                );
+            } else {
+                number.clear(batcher);
+            }
+        }
+    }
+
+    /// empty slots before the first move
+    fn offset(&self) -> usize {
+        (self.first == Side::Black) as usize
+    }
+
+    fn row(&self, ply: usize) -> usize {
+        (ply + self.offset()) / 2
+    }
+
+    fn max_scroll(&self) -> usize {
+        let rows = (self.sans.len() + self.offset()).div_ceil(2);
+        rows.saturating_sub(self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `rows` visible rows of `plies` moves, the first by `first`
+    fn moves(rows: usize, plies: usize, first: Side) -> MoveList {
+        let mut list = MoveList::new(Vec2::ZERO, rows as f32 * ROW_HEIGHT);
+        list.sans = vec![String::new(); plies];
+        list.first = first;
+        list
+    }
+
+    /// the middle of the `column` of visible row `row`
+    fn at(row: usize, column: usize) -> Vec2 {
+        Vec2::new(
+            (COLUMNS[column] as f32 + 1.0) * SIZE,
+            (row as f32 + 0.5) * ROW_HEIGHT,
+        )
+    }
+
+    #[test]
+    fn rows_and_scrolling() {
+        let list = moves(3, 7, Side::White);
+        assert_eq!(list.row(0), 0);
+        assert_eq!(list.row(1), 0);
+        assert_eq!(list.row(6), 3);
+        assert_eq!(list.max_scroll(), 1);
+
+        // black's first move shares its row with an empty white slot
+        let list = moves(3, 7, Side::Black);
+        assert_eq!(list.row(0), 0);
+        assert_eq!(list.row(1), 1);
+        assert_eq!(list.max_scroll(), 1);
+
+        assert_eq!(moves(3, 6, Side::White).max_scroll(), 0);
+        assert_eq!(moves(3, 0, Side::White).max_scroll(), 0);
+    }
+
+    #[test]
+    fn hits() {
+        let mut list = moves(3, 7, Side::Black);
+        assert_eq!(list.hit(at(0, 0)), None);
+        assert_eq!(list.hit(at(0, 1)), Some(1));
+        assert_eq!(list.hit(at(1, 0)), Some(2));
+        assert_eq!(list.hit(at(2, 1)), Some(5));
+        // the move numbers and anything below the list
+        assert_eq!(list.hit(Vec2::new(SIZE, ROW_HEIGHT / 2.0)), None);
+        assert_eq!(list.hit(at(3, 0)), None);
+        assert_eq!(list.hit(Vec2::new(COLUMNS[0] as f32 * SIZE, -SIZE)), None);
+
+        list.scroll = 1;
+        assert_eq!(list.hit(at(0, 0)), Some(2));
+        assert_eq!(list.hit(at(2, 1)), Some(7));
+    }
+}
@@ -0,0 +1,543 @@
+use crate::{
+    board::Move,
+    piece::{Piece, Side},
+    Board, BoardPos,
+};
+use core::fmt;
+
+//
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    /// the position before the first move, from the `FEN` tag if present
+    pub board: Board,
+    pub side: Side,
+    pub moves: Vec<Move>,
+    pub result: GameResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    #[default]
+    Unknown,
+}
+
+enum Token {
+    Tag(String, String),
+    San(String),
+    Result(GameResult),
+}
+
+//
+
+/// parses every game in a pgn collection
+///
+/// comments, variations and annotations are skipped, a game stops at the
+/// first move that cannot be played on this board (castling and promotions
+/// included)
+pub fn parse(text: &str) -> Vec<Game> {
+    let mut games = Vec::new();
+    let mut game: Option<Game> = None;
+    let mut broken = false;
+    // the position after the moves of `game`, replayed once per game
+    let mut position: Option<(Board, Side)> = None;
+
+    for token in tokens(text) {
+        match token {
+            Token::Tag(name, value) => {
+                position = None;
+                // a tag after the movetext starts the next game
+                if game.as_ref().is_some_and(|game| !game.moves.is_empty()) {
+                    games.extend(game.take());
+                }
+                let game = game.get_or_insert_with(|| {
+                    broken = false;
+                    Game::new()
+                });
+                if name == "FEN" {
+                    match Board::from_fen(&value) {
+                        Ok((board, side)) => {
+                            game.board = board;
+                            game.side = side;
+                        }
+                        Err(err) => log::warn!("invalid fen '{value}': {err}"),
+                    }
+                }
+                game.tags.push((name, value));
+            }
+            Token::San(san) => {
+                let game = game.get_or_insert_with(|| {
+                    broken = false;
+                    Game::new()
+                });
+                if broken {
+                    continue;
+                }
+
+                let (board, side) = position.get_or_insert_with(|| game.position());
+                match parse_san(board, *side, &san) {
+                    Some(mv) => {
+                        board.play(mv);
+                        *side = side.other();
+                        game.moves.push(mv);
+                    }
+                    None => {
+                        log::warn!("unplayable move '{san}' after {} plies", game.moves.len());
+                        broken = true;
+                    }
+                }
+            }
+            Token::Result(result) => {
+                position = None;
+                let mut game = game.take().unwrap_or_default();
+                game.result = result;
+                games.push(game);
+            }
+        }
+    }
+
+    games.extend(game);
+    games
+}
+
+/// finds the legal move written in standard algebraic notation
+pub fn parse_san(board: &Board, side: Side, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let castle = match san {
+        "O-O" | "0-0" => Some(7),
+        "O-O-O" | "0-0-0" => Some(3),
+        _ => None,
+    };
+    if let Some(file) = castle {
+        return board.legal_moves(side).into_iter().find(|mv| {
+            board.get_piece(&mv.from) == Some((side, Piece::King))
+                && mv.from.file == 5
+                && mv.to.file == file
+        });
+    }
+
+    // `e8=Q` and `e8Q`
+    let (san, promotion) = match san.char_indices().last()? {
+        (i, c @ ('N' | 'B' | 'R' | 'Q')) if i > 0 => {
+            (san[..i].trim_end_matches('='), Some(Piece::from_char(c)?.1))
+        }
+        _ => (san, None),
+    };
+
+    let (piece, rest) = match san.chars().next()? {
+        'N' => (Piece::Knight, &san[1..]),
+        'B' => (Piece::Bishop, &san[1..]),
+        'R' => (Piece::Rook, &san[1..]),
+        'Q' => (Piece::Queen, &san[1..]),
+        'K' => (Piece::King, &san[1..]),
+        _ => (Piece::Pawn, san),
+    };
+    let rest = rest.replace('x', "");
+    if !rest.is_ascii() || rest.len() < 2 {
+        return None;
+    }
+
+    let (from_hint, to) = rest.split_at(rest.len() - 2);
+    let to = BoardPos::parse(to)?;
+    let mut file = None;
+    let mut rank = None;
+    for c in from_hint.chars() {
+        match c {
+            'a'..='h' => file = Some(c as u8 - b'a' + 1),
+            '1'..='8' => rank = Some(c as u8 - b'0'),
+            _ => return None,
+        }
+    }
+
+    let mut candidates = board.legal_moves(side).into_iter().filter(|mv| {
+        mv.to == to
+            && board.get_piece(&mv.from) == Some((side, piece))
+            && mv.promotion == promotion
+            && file.is_none_or(|file| mv.from.file == file)
+            && rank.is_none_or(|rank| mv.from.rank == rank)
+    });
+    let mv = candidates.next()?;
+    candidates.next().is_none().then_some(mv)
+}
+
+/// the move in standard algebraic notation, `mv` has to be legal
+pub fn to_san(board: &Board, side: Side, mv: Move) -> String {
+    let (_, piece) = match board.get_piece(&mv.from) {
+        Some(piece) => piece,
+        None => return mv.to_string(),
+    };
+    // en passant takes on an empty square
+    let capture =
+        board.get_piece(&mv.to).is_some() || (piece == Piece::Pawn && mv.from.file != mv.to.file);
+
+    let mut san = String::new();
+    if piece == Piece::King && mv.from.file.abs_diff(mv.to.file) == 2 {
+        san.push_str(if mv.to.file == 7 { "O-O" } else { "O-O-O" });
+    } else {
+        if piece == Piece::Pawn {
+            if capture {
+                san.push((b'a' + mv.from.file - 1) as char);
+            }
+        } else {
+            san.push(piece.to_char(Side::White));
+
+            // other pieces of the same kind that could go there too
+            let others = board
+                .legal_moves(side)
+                .into_iter()
+                .filter(|other| {
+                    other.to == mv.to
+                        && other.from != mv.from
+                        && board.get_piece(&other.from) == Some((side, piece))
+                })
+                .collect::<Vec<_>>();
+            if !others.is_empty() {
+                let file = (b'a' + mv.from.file - 1) as char;
+                let rank = (b'0' + mv.from.rank) as char;
+                if others.iter().all(|other| other.from.file != mv.from.file) {
+                    san.push(file);
+                } else if others.iter().all(|other| other.from.rank != mv.from.rank) {
+                    san.push(rank);
+                } else {
+                    san.push(file);
+                    san.push(rank);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&mv.to.to_string());
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push(promotion.to_char(Side::White));
+        }
+    }
+
+    let mut board = board.clone();
+    board.play(mv);
+    if board.in_check(side.other()) {
+        if board.legal_moves(side.other()).is_empty() {
+            san.push('#');
+        } else {
+            san.push('+');
+        }
+    }
+    san
+}
+
+/// the tag pairs, `\\` and `"` in values are escaped
+pub(crate) fn write_tags(f: &mut fmt::Formatter<'_>, tags: &[(String, String)]) -> fmt::Result {
+    for (name, value) in tags {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(f, "[{name} \"{value}\"]")?;
+    }
+    Ok(())
+}
+
+/// the rest of a `[Name "value"]` tag after the `[`, `None` without a name
+pub(crate) fn read_tag(chars: &mut impl Iterator<Item = char>) -> Option<(String, String)> {
+    let mut name = String::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut named = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => value.extend(chars.next()),
+            '"' => {
+                quoted = !quoted;
+                named = true;
+            }
+            ']' if !quoted => break,
+            c if quoted => value.push(c),
+            c if !named && !c.is_whitespace() => name.push(c),
+            _ => {}
+        }
+    }
+    (!name.is_empty()).then_some((name, value))
+}
+
+/// the tokens separated by spaces, export format lines stay under 80
+/// columns
+pub(crate) fn write_movetext(f: &mut fmt::Formatter<'_>, tokens: Vec<String>) -> fmt::Result {
+    let mut column = 0;
+    for token in tokens {
+        if column > 0 && column + 1 + token.len() > 79 {
+            writeln!(f)?;
+            column = 0;
+        } else if column > 0 {
+            f.write_str(" ")?;
+            column += 1;
+        }
+        f.write_str(&token)?;
+        column += token.len();
+    }
+    writeln!(f)
+}
+
+fn tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut variation = 0;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => variation += 1,
+            ')' => variation = (variation - 1).max(0),
+            '[' if variation == 0 => {
+                if let Some((name, value)) = read_tag(&mut chars) {
+                    tokens.push(Token::Tag(name, value));
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if variation != 0 || word.starts_with('$') {
+                    continue;
+                }
+
+                if let Some(result) = GameResult::parse(&word) {
+                    tokens.push(Token::Result(result));
+                    continue;
+                }
+
+                // `12.`, `12...` and `12.e4`
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if !san.is_empty() {
+                    tokens.push(Token::San(san.to_string()));
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            board: Board::starting(),
+            side: Side::White,
+            moves: Vec::new(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// the position after every move of the game
+    pub fn position(&self) -> (Board, Side) {
+        let mut board = self.board.clone();
+        let mut side = self.side;
+        for &mv in self.moves.iter() {
+            board.play(mv);
+            side = side.other();
+        }
+        (board, side)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// pgn export, tags as stored followed by the movetext in san
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tags(f, &self.tags)?;
+        writeln!(f)?;
+
+        let mut tokens = Vec::with_capacity(self.moves.len() * 3 / 2 + 1);
+        let mut board = self.board.clone();
+        let mut side = self.side;
+        let mut number = board.fullmove();
+        for (i, &mv) in self.moves.iter().enumerate() {
+            match side {
+                Side::White => tokens.push(format!("{number}.")),
+                Side::Black if i == 0 => tokens.push(format!("{number}...")),
+                Side::Black => {}
+            }
+            tokens.push(to_san(&board, side, mv));
+            board.play(mv);
+            if side == Side::Black {
+                number += 1;
+            }
+            side = side.other();
+        }
+        tokens.push(self.result.to_string());
+        write_movetext(f, tokens)
+    }
+}
+
+impl GameResult {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn winner(self) -> Option<Side> {
+        match self {
+            GameResult::WhiteWins => Some(Side::White),
+            GameResult::BlackWins => Some(Side::Black),
+            GameResult::Draw | GameResult::Unknown => None,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*",
+        })
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, from: &str, to: &str) -> String {
+        let (board, side) = Board::from_fen(fen).unwrap();
+        let mv = Move {
+            from: BoardPos::parse(from).unwrap(),
+            to: BoardPos::parse(to).unwrap(),
+            promotion: None,
+        };
+        assert!(board.legal_moves(side).contains(&mv));
+        let san = to_san(&board, side, mv);
+        assert_eq!(parse_san(&board, side, &san), Some(mv), "{san}");
+        san
+    }
+
+    #[test]
+    fn disambiguation() {
+        // by file, by rank and by both
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san(knights, "b1", "d2"), "Nbd2");
+        assert_eq!(san(knights, "f1", "d2"), "Nfd2");
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(rooks, "a1", "a3"), "R1a3");
+        assert_eq!(san(rooks, "a5", "a3"), "R5a3");
+        let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san(queens, "a1", "b2"), "Qa1b2");
+
+        // an ambiguous move is not guessed
+        let (board, side) = Board::from_fen(knights).unwrap();
+        assert_eq!(parse_san(&board, side, "Nd2"), None);
+    }
+
+    #[test]
+    fn captures_and_suffixes() {
+        let start = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w - - 0 2";
+        assert_eq!(san(start, "e4", "d5"), "exd5");
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a8"), "Ra8+");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1", "a8"), "Ra8#");
+
+        let (board, side) = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert!(parse_san(&board, side, "Ra8#!").is_some());
+        assert_eq!(parse_san(&board, side, "O-O"), None);
+    }
+
+    #[test]
+    fn castling_promotion_and_en_passant() {
+        let san = |fen: &str, mv: &str| {
+            let (board, side) = Board::from_fen(fen).unwrap();
+            let mv = Move::parse(mv).unwrap();
+            assert!(board.legal_moves(side).contains(&mv));
+            let san = to_san(&board, side, mv);
+            assert_eq!(parse_san(&board, side, &san), Some(mv), "{san}");
+            san
+        };
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castles, "e1g1"), "O-O");
+        assert_eq!(san(castles, "e1c1"), "O-O-O");
+        let promotes = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(promotes, "a7a8q"), "a8=Q");
+        assert_eq!(san(promotes, "a7b8n"), "axb8=N");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+
+        let (board, side) = Board::from_fen(promotes).unwrap();
+        assert_eq!(parse_san(&board, side, "a8Q"), Move::parse("a7a8q"));
+        // a promotion needs its piece
+        assert_eq!(parse_san(&board, side, "a8"), None);
+
+        let games =
+            parse("1. e4 d5 2. e5 f5 3. exf6 Nxf6 4. Nf3 Nc6 5. Bc4 Bf5 6. O-O Qd7 7. d3 O-O-O *");
+        assert_eq!(games[0].moves.len(), 14);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let text = "[Event \"the \\\"big\\\" one\"]\n\
+             [Site \"C:\\\\games\"]\n\
+             [Round \"a ] in it\"]\n\
+             \n\
+             1. e4 e5 2. Nf3 {a comment} Nc6 (2... d6) 3. Bb5 a6 $1 1-0\n\
+             \n\
+             [FEN \"4k3/8/8/8/8/8/8/R3K3 b - - 0 1\"]\n\
+             \n\
+             1... Kd7 2. Ra7+ *\n";
+        let games = parse(text);
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("the \"big\" one"));
+        assert_eq!(games[0].tag("Site"), Some("C:\\games"));
+        assert_eq!(games[0].tag("Round"), Some("a ] in it"));
+        assert_eq!(games[0].moves.len(), 6);
+        assert_eq!(games[0].result, GameResult::WhiteWins);
+        assert_eq!(games[1].side, Side::Black);
+        assert_eq!(games[1].moves.len(), 2);
+
+        let written = games.iter().map(Game::to_string).collect::<Vec<_>>();
+        assert_eq!(parse(&written.join("\n")), games);
+    }
+
+    #[test]
+    fn unplayable_moves_end_the_game() {
+        let games = parse("1. e4 e5 2. Ke3 Nc6 3. Nf3 *");
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves.len(), 2);
+    }
+}
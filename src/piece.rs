@@ -34,6 +34,51 @@ impl Side {
 }
 
 impl Piece {
+    /// fen letter, upper case is white
+    pub const fn from_char(c: char) -> Option<(Side, Self)> {
+        let side = if c.is_ascii_uppercase() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        let piece = match c.to_ascii_lowercase() {
+            'p' => Piece::Pawn,
+            'n' => Piece::Knight,
+            'b' => Piece::Bishop,
+            'r' => Piece::Rook,
+            'q' => Piece::Queen,
+            'k' => Piece::King,
+            _ => return None,
+        };
+        Some((side, piece))
+    }
+
+    pub const fn to_char(self, side: Side) -> char {
+        let c = match self {
+            Piece::Pawn => 'p',
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Rook => 'r',
+            Piece::Queen => 'q',
+            Piece::King => 'k',
+        };
+        match side {
+            Side::White => c.to_ascii_uppercase(),
+            Side::Black => c,
+        }
+    }
+
+    pub const fn value(self) -> i32 {
+        match self {
+            Piece::Pawn => 100,
+            Piece::Knight => 320,
+            Piece::Bishop => 330,
+            Piece::Rook => 500,
+            Piece::Queen => 900,
+            Piece::King => 0,
+        }
+    }
+
     pub fn moves(
         self,
         board: &Board,
@@ -72,6 +117,9 @@ impl Piece {
                 let first_move = BoardPos::new(x, y - 2);
                 let first_move = if pos.rank == 7
                     && first_move.and_then(|pos| board.get_piece(&pos)).is_none()
+                    && BoardPos::new(x, y - 1)
+                        .and_then(|pos| board.get_piece(&pos))
+                        .is_none()
                 {
                     Some(first_move)
                 } else {
@@ -122,6 +170,9 @@ impl Piece {
                 let first_move = BoardPos::new(x, y + 2);
                 let first_move = if pos.rank == 2
                     && first_move.and_then(|pos| board.get_piece(&pos)).is_none()
+                    && BoardPos::new(x, y + 1)
+                        .and_then(|pos| board.get_piece(&pos))
+                        .is_none()
                 {
                     Some(first_move)
                 } else {
@@ -0,0 +1,159 @@
+use chess::{clock::TimeControl, strength::Difficulty};
+use core::fmt;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+//
+
+/// who makes the moves of one side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Player {
+    Human,
+    /// the built in search
+    Engine(Difficulty),
+    /// path to a uci engine executable
+    External(String),
+}
+
+/// players picked on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerArgs {
+    /// indexed by side
+    pub players: [Player; 2],
+    /// external engines the menu can cycle through
+    pub engines: Vec<String>,
+    /// uci engine that analyses every position of the game
+    pub analyser: Option<String>,
+    /// `None` plays without clocks
+    pub time_control: Option<TimeControl>,
+    /// time between autoplay steps of a replay
+    pub replay_interval: Duration,
+    /// where games are saved and loaded
+    pub games_dir: PathBuf,
+}
+
+//
+
+impl Player {
+    /// `human`, `ai`, `ai:<difficulty>` or `uci:<path>`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.split_once(':') {
+            None if s == "human" => Some(Player::Human),
+            None if s == "ai" => Some(Player::Engine(Difficulty::default())),
+            Some(("ai", difficulty)) => Difficulty::parse(difficulty).map(Player::Engine),
+            Some(("uci", path)) => Some(Player::External(path.to_string())),
+            _ => None,
+        }
+    }
+
+    /// the choice after this one in the menu
+    pub fn next(&self, engines: &[String]) -> Self {
+        let choices = [Player::Human]
+            .into_iter()
+            .chain(Difficulty::ALL.map(Player::Engine))
+            .chain(engines.iter().cloned().map(Player::External))
+            .collect::<Vec<_>>();
+        let i = choices
+            .iter()
+            .position(|choice| choice == self)
+            .unwrap_or(0);
+        choices[(i + 1) % choices.len()].clone()
+    }
+
+    pub fn is_human(&self) -> bool {
+        *self == Player::Human
+    }
+}
+
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Player::Human => f.write_str("human"),
+            Player::Engine(difficulty) => write!(f, "ai {difficulty}"),
+            Player::External(path) => {
+                let path = Path::new(path);
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                write!(f, "uci {}", name.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl PlayerArgs {
+    /// `[--white <player>] [--black <player>] [--engine <path>]...
+    /// [--analyse <path>] [--time <control>] [--replay-interval <secs>]
+    /// [--games <dir>]`, the human plays white against the full strength ai
+    /// without clocks and games are saved in `games` by default
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut res = Self {
+            players: [Player::Human, Player::Engine(Difficulty::Full)],
+            engines: Vec::new(),
+            analyser: None,
+            time_control: None,
+            replay_interval: Duration::from_secs(1),
+            games_dir: PathBuf::from("games"),
+        };
+
+        while let Some(arg) = args.next() {
+            let side = match arg.as_str() {
+                "--white" => 0,
+                "--black" => 1,
+                "--engine" => {
+                    res.engines.extend(args.next());
+                    continue;
+                }
+                "--analyse" => {
+                    res.analyser = args.next();
+                    continue;
+                }
+                "--time" => {
+                    let value = args.next().unwrap_or_default();
+                    res.time_control = TimeControl::parse(&value);
+                    if res.time_control.is_none() {
+                        log::warn!("invalid time control '{value}'");
+                    }
+                    continue;
+                }
+                "--games" => {
+                    if let Some(dir) = args.next() {
+                        res.games_dir = PathBuf::from(dir);
+                    }
+                    continue;
+                }
+                "--replay-interval" => {
+                    let value = args.next().unwrap_or_default();
+                    let interval = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|&secs| secs > 0.0)
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+                    match interval {
+                        Some(interval) => res.replay_interval = interval,
+                        None => log::warn!("invalid interval '{value}'"),
+                    }
+                    continue;
+                }
+                _ => {
+                    log::warn!("unknown argument '{arg}'");
+                    continue;
+                }
+            };
+            let value = args.next().unwrap_or_default();
+            match Player::parse(&value) {
+                Some(player) => {
+                    if let Player::External(path) = &player {
+                        res.engines.push(path.clone());
+                    }
+                    res.players[side] = player;
+                }
+                None => log::warn!("invalid player '{value}'"),
+            }
+        }
+
+        res.engines.sort();
+        res.engines.dedup();
+        res
+    }
+}
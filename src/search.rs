@@ -0,0 +1,1037 @@
+use crate::{
+    board::Move,
+    eval::{evaluate, is_mate, MATE},
+    piece::{Piece, Side},
+    strength::Strength,
+    tablebase::Tablebase,
+    time::{Limits, Pondering, Stop, TimeManager},
+    tt::{Bound, TranspositionTable, TtEntry},
+    Board,
+};
+use rand::seq::SliceRandom;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+//
+
+/// progress report sent for every line after every finished iteration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchInfo {
+    /// 1 based rank of the line
+    pub multipv: usize,
+    pub depth: u8,
+    pub seldepth: u8,
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchResult {
+    pub best: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+    /// best first, one per multi pv line
+    pub lines: Vec<AnalysisLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AnalysisLine {
+    pub score: i32,
+    pub depth: u8,
+    pub seldepth: u8,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub threads: usize,
+    /// transposition table size in MiB
+    pub hash: usize,
+    /// number of best lines to search
+    pub multi_pv: usize,
+    pub techniques: Techniques,
+    pub strength: Strength,
+}
+
+/// search enhancements that can be switched off one by one to measure what
+/// each of them is worth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Techniques {
+    pub null_move: bool,
+    pub lmr: bool,
+    pub futility: bool,
+    pub reverse_futility: bool,
+    pub aspiration: bool,
+    pub check_extension: bool,
+    pub pvs: bool,
+}
+
+/// lazy smp search pool
+///
+/// every thread runs its own iterative deepening on the same root and they
+/// only talk through the shared transposition table, the main thread decides
+/// when to stop and its result is the one returned
+pub struct Search {
+    options: SearchOptions,
+    tt: TranspositionTable,
+    tablebase: Option<Arc<Tablebase>>,
+}
+
+struct Worker<'a> {
+    board: Board,
+    techniques: Techniques,
+    eval_noise: i32,
+    /// picks the noise of each position, new for every search
+    noise_seed: u64,
+    tt: &'a TranspositionTable,
+    tablebase: Option<&'a Tablebase>,
+    time: TimeManager,
+    nodes: u64,
+    seldepth: u8,
+    total_nodes: &'a AtomicU64,
+    aborted: bool,
+    /// root moves skipped by the current multi pv line
+    excluded: Vec<Move>,
+}
+
+//
+
+const INF: i32 = MATE + 1;
+
+pub const MAX_DEPTH: u8 = 64;
+
+pub const MAX_THREADS: usize = 256;
+
+//
+
+/// single threaded search with a fresh transposition table
+///
+/// with depth or node [`Limits`] the result is the same on every run
+pub fn search(
+    board: &Board,
+    side: Side,
+    limits: Limits,
+    stop: Stop,
+    report: impl FnMut(&SearchInfo),
+) -> SearchResult {
+    Search::new(SearchOptions::default()).go(board, side, limits, stop, report)
+}
+
+/// the `lines` best moves with their principal variations, best first
+pub fn analyse(board: &Board, side: Side, limits: Limits, lines: usize) -> Vec<AnalysisLine> {
+    let options = SearchOptions {
+        multi_pv: lines,
+        ..Default::default()
+    };
+    Search::new(options)
+        .go(board, side, limits, Stop::new(), |_| {})
+        .lines
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            hash: 16,
+            multi_pv: 1,
+            techniques: Techniques::default(),
+            strength: Strength::FULL,
+        }
+    }
+}
+
+impl Techniques {
+    /// plain alpha-beta
+    pub const NONE: Self = Self {
+        null_move: false,
+        lmr: false,
+        futility: false,
+        reverse_futility: false,
+        aspiration: false,
+        check_extension: false,
+        pvs: false,
+    };
+
+    pub const ALL: Self = Self {
+        null_move: true,
+        lmr: true,
+        futility: true,
+        reverse_futility: true,
+        aspiration: true,
+        check_extension: true,
+        pvs: true,
+    };
+
+    pub const NAMES: [&'static str; 7] = [
+        "NullMove",
+        "LMR",
+        "Futility",
+        "ReverseFutility",
+        "Aspiration",
+        "CheckExtension",
+        "PVS",
+    ];
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let on = match name {
+            "NullMove" => self.null_move,
+            "LMR" => self.lmr,
+            "Futility" => self.futility,
+            "ReverseFutility" => self.reverse_futility,
+            "Aspiration" => self.aspiration,
+            "CheckExtension" => self.check_extension,
+            "PVS" => self.pvs,
+            _ => return None,
+        };
+        Some(on)
+    }
+
+    /// toggles a technique by its option name, `false` if there is no such
+    /// technique
+    pub fn set(&mut self, name: &str, on: bool) -> bool {
+        let technique = match name {
+            "NullMove" => &mut self.null_move,
+            "LMR" => &mut self.lmr,
+            "Futility" => &mut self.futility,
+            "ReverseFutility" => &mut self.reverse_futility,
+            "Aspiration" => &mut self.aspiration,
+            "CheckExtension" => &mut self.check_extension,
+            "PVS" => &mut self.pvs,
+            _ => return false,
+        };
+        *technique = on;
+        true
+    }
+}
+
+impl Default for Techniques {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Search {
+    pub fn new(options: SearchOptions) -> Self {
+        Self {
+            tt: TranspositionTable::new(options.hash),
+            options: SearchOptions {
+                threads: options.threads.clamp(1, MAX_THREADS),
+                ..options
+            },
+            tablebase: None,
+        }
+    }
+
+    pub fn options(&self) -> SearchOptions {
+        self.options
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.options.threads = threads.clamp(1, MAX_THREADS);
+    }
+
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.options.multi_pv = multi_pv.max(1);
+    }
+
+    pub fn set_techniques(&mut self, techniques: Techniques) {
+        self.options.techniques = techniques;
+    }
+
+    /// plays weaker, see [`Strength`]
+    pub fn set_strength(&mut self, strength: Strength) {
+        self.options.strength = strength;
+    }
+
+    pub fn set_hash(&mut self, hash: usize) {
+        if hash != self.options.hash {
+            self.options.hash = hash;
+            self.tt = TranspositionTable::new(hash);
+        }
+    }
+
+    /// endgame tables probed at the root and inside the tree
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn tablebase(&self) -> Option<&Arc<Tablebase>> {
+        self.tablebase.as_ref()
+    }
+
+    /// forget everything learned, for a new game or reproducible results
+    pub fn clear(&self) {
+        self.tt.clear();
+    }
+
+    pub fn hashfull(&self) -> usize {
+        self.tt.hashfull()
+    }
+
+    /// iterative deepening search of `board` with `side` to move
+    ///
+    /// runs until one of the `limits` is hit or `stop` is signaled, `report`
+    /// is called by the main thread after every finished iteration
+    pub fn go(
+        &self,
+        board: &Board,
+        side: Side,
+        limits: Limits,
+        stop: Stop,
+        report: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        self.go_ponder(board, side, limits, stop, Pondering::default(), report)
+    }
+
+    /// same as [`Search::go`] but the `limits` only start to count after
+    /// `pondering` gets a ponder hit, until then it runs like an infinite
+    /// search
+    pub fn go_ponder(
+        &self,
+        board: &Board,
+        side: Side,
+        limits: Limits,
+        stop: Stop,
+        pondering: Pondering,
+        mut report: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let strength = self.options.strength;
+        if strength.random {
+            let moves = board.legal_moves(side);
+            let best = moves.choose(&mut rand::thread_rng()).copied();
+            return SearchResult {
+                best,
+                pv: best.into_iter().collect(),
+                ..Default::default()
+            };
+        }
+
+        let limits = Limits {
+            depth: match (limits.depth, strength.max_depth) {
+                (Some(depth), Some(max)) => Some(depth.min(max)),
+                (depth, max) => depth.or(max),
+            },
+            ..limits
+        };
+        let noise_seed = if strength.eval_noise == 0 {
+            0
+        } else {
+            rand::random()
+        };
+
+        let total_nodes = AtomicU64::new(0);
+        let helpers_stop = Stop::new();
+
+        thread::scope(|s| {
+            for id in 1..self.options.threads {
+                let helper_limits = Limits {
+                    depth: limits.depth,
+                    ..Default::default()
+                };
+                let mut helper = Worker::new(
+                    board,
+                    &self.options,
+                    noise_seed,
+                    &self.tt,
+                    self.tablebase.as_deref(),
+                    TimeManager::new(helper_limits, helpers_stop.clone()),
+                    &total_nodes,
+                );
+                thread::Builder::new()
+                    .name(format!("search helper {id}"))
+                    .spawn_scoped(s, move || {
+                        // odd helpers run one ply ahead to spread the work
+                        helper.iterate(side, 1 + (id % 2) as u8, 1, &mut |_| {});
+                    })
+                    .expect("failed to spawn a search thread");
+            }
+
+            let mut main = Worker::new(
+                board,
+                &self.options,
+                noise_seed,
+                &self.tt,
+                self.tablebase.as_deref(),
+                TimeManager::with_pondering(limits, stop, pondering),
+                &total_nodes,
+            );
+            let mut result = main.iterate(side, 1, self.options.multi_pv, &mut report);
+            helpers_stop.stop();
+
+            result.nodes = total_nodes.load(Ordering::Relaxed);
+            result
+        })
+    }
+}
+
+impl<'a> Worker<'a> {
+    fn new(
+        board: &Board,
+        options: &SearchOptions,
+        noise_seed: u64,
+        tt: &'a TranspositionTable,
+        tablebase: Option<&'a Tablebase>,
+        time: TimeManager,
+        total_nodes: &'a AtomicU64,
+    ) -> Self {
+        Self {
+            board: board.clone(),
+            techniques: options.techniques,
+            eval_noise: options.strength.eval_noise,
+            noise_seed,
+            tt,
+            tablebase,
+            time,
+            nodes: 0,
+            seldepth: 0,
+            total_nodes,
+            aborted: false,
+            excluded: Vec::new(),
+        }
+    }
+
+    fn iterate(
+        &mut self,
+        side: Side,
+        start: u8,
+        multi_pv: usize,
+        report: &mut dyn FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let mut result = SearchResult::default();
+        let root_moves = self.board.legal_moves(side);
+        if root_moves.is_empty() {
+            result.score = if self.board.in_check(side) { -MATE } else { 0 };
+            return result;
+        }
+        // always have something to play
+        result.best = root_moves.first().copied();
+        let multi_pv = multi_pv.clamp(1, root_moves.len());
+
+        // the tables already know the perfect move
+        if multi_pv == 1 {
+            if let Some((best, wdl)) = self
+                .tablebase
+                .and_then(|tablebase| tablebase.best_move(&self.board, side))
+            {
+                let line = AnalysisLine {
+                    score: wdl.score(0),
+                    depth: 1,
+                    seldepth: 1,
+                    nodes: 1,
+                    pv: vec![best],
+                };
+                report(&SearchInfo {
+                    multipv: 1,
+                    depth: line.depth,
+                    seldepth: line.seldepth,
+                    score: line.score,
+                    nodes: line.nodes,
+                    time: self.time.elapsed(),
+                    pv: line.pv.clone(),
+                });
+                return SearchResult {
+                    best: Some(best),
+                    score: line.score,
+                    depth: line.depth,
+                    nodes: line.nodes,
+                    pv: line.pv.clone(),
+                    lines: vec![line],
+                };
+            }
+        }
+
+        'deepening: for depth in start..=MAX_DEPTH {
+            if !self.time.should_start(depth) {
+                break;
+            }
+
+            // every line excludes the first moves of the lines before it
+            self.excluded.clear();
+            let mut lines = Vec::with_capacity(multi_pv);
+            for i in 0..multi_pv {
+                let previous = result.lines.get(i);
+                let mut pv = previous.map(|line| line.pv.clone()).unwrap_or_default();
+                let previous_score = previous.map_or(0, |line| line.score);
+
+                self.seldepth = 0;
+                let score = self.aspiration(side, depth, previous_score, &mut pv);
+
+                // an aborted iteration is thrown away
+                if self.aborted {
+                    break 'deepening;
+                }
+
+                let best = match pv.first() {
+                    Some(best) => *best,
+                    None => break,
+                };
+                self.excluded.push(best);
+                lines.push(AnalysisLine {
+                    score,
+                    depth,
+                    seldepth: self.seldepth,
+                    nodes: self.node_count(),
+                    pv,
+                });
+            }
+            self.excluded.clear();
+            lines.sort_by_key(|line| -line.score);
+
+            let (best, score) = match lines.first() {
+                Some(line) => (line.pv.first().copied(), line.score),
+                None => break,
+            };
+            let best_move_changed = depth > start && best != result.best;
+            let score_drop = if depth > start {
+                result.score - score
+            } else {
+                0
+            };
+            self.time.update(best_move_changed, score_drop);
+
+            for (i, line) in lines.iter().enumerate() {
+                report(&SearchInfo {
+                    multipv: i + 1,
+                    depth,
+                    seldepth: line.seldepth,
+                    score: line.score,
+                    nodes: self.node_count(),
+                    time: self.time.elapsed(),
+                    pv: line.pv.clone(),
+                });
+            }
+
+            result = SearchResult {
+                best,
+                score,
+                depth,
+                nodes: self.nodes,
+                pv: lines[0].pv.clone(),
+                lines,
+            };
+
+            // no reason to look further after a forced mate was found
+            if multi_pv == 1 && is_mate(score) && self.time.limits().depth.is_none() {
+                break;
+            }
+        }
+
+        self.total_nodes
+            .fetch_add(self.nodes & 1023, Ordering::Relaxed);
+        result
+    }
+
+    fn node_count(&self) -> u64 {
+        self.total_nodes.load(Ordering::Relaxed) + (self.nodes & 1023)
+    }
+
+    /// searches a narrow window around the previous score and widens it
+    /// until the score lands inside
+    fn aspiration(&mut self, side: Side, depth: u8, previous: i32, pv: &mut Vec<Move>) -> i32 {
+        if !self.techniques.aspiration || depth < 4 || is_mate(previous) {
+            return self.negamax(side, depth, 0, -INF, INF, true, pv);
+        }
+
+        let mut delta = 25;
+        let mut alpha = previous - delta;
+        let mut beta = previous + delta;
+        loop {
+            let score = self.negamax(side, depth, 0, alpha, beta, true, pv);
+            if self.aborted {
+                return score;
+            }
+
+            if score <= alpha {
+                alpha = (alpha - delta).max(-INF);
+            } else if score >= beta {
+                beta = (beta + delta).min(INF);
+            } else {
+                return score;
+            }
+            delta *= 2;
+        }
+    }
+
+    /// static eval with the strength noise, the same position always gets the
+    /// same noise during one search
+    fn evaluate(&self, side: Side) -> i32 {
+        let eval = evaluate(&self.board, side);
+        if self.eval_noise == 0 {
+            return eval;
+        }
+        let hash = (self.board.hash(side) ^ self.noise_seed).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let range = 2 * self.eval_noise as u64 + 1;
+        eval + ((hash >> 32) % range) as i32 - self.eval_noise
+    }
+
+    fn count_node(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes & 1023 == 0 {
+            self.total_nodes.fetch_add(1024, Ordering::Relaxed);
+        }
+
+        if self.time.should_stop(self.nodes) {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        side: Side,
+        mut depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+        allow_null: bool,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        if self.count_node() {
+            return 0;
+        }
+
+        let techniques = self.techniques;
+        let pv_node = beta - alpha > 1;
+        let in_check = self.board.in_check(side);
+
+        if in_check && techniques.check_extension && ply < MAX_DEPTH {
+            depth += 1;
+        }
+
+        if depth == 0 || ply >= MAX_DEPTH {
+            pv.clear();
+            return self.quiescence(side, ply, alpha, beta);
+        }
+
+        let hash = self.board.hash(side);
+        let entry = self.tt.probe(hash, ply);
+        if let Some(entry) = entry {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            // pv nodes always search to fill in the principal variation
+            if !pv_node && entry.depth >= depth && cutoff {
+                pv.clear();
+                pv.extend(entry.best);
+                return entry.score;
+            }
+        }
+
+        // few enough pieces left for perfect knowledge
+        if ply > 0 {
+            if let Some(wdl) = self
+                .tablebase
+                .and_then(|tablebase| tablebase.probe_wdl(&self.board, side))
+            {
+                pv.clear();
+                return wdl.score(ply);
+            }
+        }
+
+        let eval = if in_check { -INF } else { self.evaluate(side) };
+        let prunable = !pv_node && !in_check && !is_mate(beta);
+
+        // reverse futility: far above beta, assume some move keeps it there
+        if techniques.reverse_futility && prunable && depth <= 6 && eval - 80 * depth as i32 >= beta
+        {
+            return eval;
+        }
+
+        // null move: passing still beats beta, a real move would too
+        if techniques.null_move
+            && prunable
+            && allow_null
+            && depth >= 3
+            && eval >= beta
+            && self.has_pieces(side)
+        {
+            let reduction = 3 + depth / 6;
+            // the opponent can't take en passant after a pass
+            let en_passant = self.board.set_en_passant(None);
+            let score = -self.negamax(
+                side.other(),
+                depth.saturating_sub(reduction),
+                ply + 1,
+                -beta,
+                -beta + 1,
+                false,
+                &mut Vec::new(),
+            );
+            self.board.set_en_passant(en_passant);
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta && !is_mate(score) {
+                return score;
+            }
+        }
+
+        let mut moves = self.board.legal_moves(side);
+        if moves.is_empty() {
+            pv.clear();
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        if ply == 0 {
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        let first = pv.first().copied().or(entry.and_then(|entry| entry.best));
+        self.order(&mut moves, first);
+
+        // futility: quiet moves cannot lift a hopeless eval up to alpha
+        let futile = techniques.futility
+            && prunable
+            && depth <= 3
+            && eval + 100 + 150 * depth as i32 <= alpha;
+
+        let old_alpha = alpha;
+        let mut best = -INF;
+        let mut best_move = None;
+        let mut child_pv = Vec::new();
+        for (i, mv) in moves.into_iter().enumerate() {
+            // follow the previous principal variation first
+            child_pv.clear();
+            if pv.first() == Some(&mv) {
+                child_pv.extend(pv.iter().skip(1));
+            }
+
+            let captured = self.board.play(mv);
+            let quiet = captured.is_none() && mv.promotion.is_none();
+            let gives_check = self.board.in_check(side.other());
+
+            if futile && i > 0 && quiet && !gives_check {
+                self.board.unplay(mv, captured);
+                continue;
+            }
+
+            let reduction = if techniques.lmr
+                && depth >= 3
+                && i >= if pv_node { 4 } else { 2 }
+                && quiet
+                && !in_check
+                && !gives_check
+            {
+                let r = 0.75 + (depth as f32).ln() * (i as f32).ln() / 2.25;
+                (r as u8).min(depth - 2)
+            } else {
+                0
+            };
+
+            let score = if i == 0 {
+                -self.negamax(
+                    side.other(),
+                    depth - 1,
+                    ply + 1,
+                    -beta,
+                    -alpha,
+                    true,
+                    &mut child_pv,
+                )
+            } else {
+                // null window for everything after the first move, reduced
+                // searches are verified at full depth when they beat alpha
+                let window = if techniques.pvs { alpha + 1 } else { beta };
+                let mut score = -self.negamax(
+                    side.other(),
+                    depth - 1 - reduction,
+                    ply + 1,
+                    -window,
+                    -alpha,
+                    true,
+                    &mut child_pv,
+                );
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(
+                        side.other(),
+                        depth - 1,
+                        ply + 1,
+                        -window,
+                        -alpha,
+                        true,
+                        &mut child_pv,
+                    );
+                }
+                if score > alpha && score < beta && window != beta {
+                    score = -self.negamax(
+                        side.other(),
+                        depth - 1,
+                        ply + 1,
+                        -beta,
+                        -alpha,
+                        true,
+                        &mut child_pv,
+                    );
+                }
+                score
+            };
+            self.board.unplay(mv, captured);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend(child_pv.iter());
+                }
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        // every move was pruned
+        if best_move.is_none() {
+            return alpha;
+        }
+
+        // a root search with excluded moves does not know the real best move
+        if ply == 0 && !self.excluded.is_empty() {
+            return best;
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > old_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(
+            hash,
+            ply,
+            TtEntry {
+                best: best_move,
+                score: best,
+                depth,
+                bound,
+            },
+        );
+
+        best
+    }
+
+    /// null move is unsafe in pawn endings because of zugzwang
+    fn has_pieces(&self, side: Side) -> bool {
+        self.board
+            .iter()
+            .any(|(s, piece, _)| s == side && !matches!(piece, Piece::Pawn | Piece::King))
+    }
+
+    fn quiescence(&mut self, side: Side, ply: u8, mut alpha: i32, beta: i32) -> i32 {
+        if self.count_node() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply);
+
+        let stand_pat = self.evaluate(side);
+        if stand_pat >= beta || ply >= MAX_DEPTH * 2 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut captures = self
+            .board
+            .legal_moves(side)
+            .into_iter()
+            .filter(|mv| self.board.get_piece(&mv.to).is_some())
+            .collect::<Vec<_>>();
+        self.order(&mut captures, None);
+
+        for mv in captures {
+            let captured = self.board.play(mv);
+            let score = -self.quiescence(side.other(), ply + 1, -beta, -alpha);
+            self.board.unplay(mv, captured);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    /// pv or hash move first, then captures by most valuable victim and
+    /// least valuable attacker, quiet moves keep their generation order
+    fn order(&self, moves: &mut [Move], first: Option<Move>) {
+        moves.sort_by_cached_key(|mv| {
+            if Some(*mv) == first {
+                return i32::MIN;
+            }
+            match (self.board.get_piece(&mv.to), self.board.get_piece(&mv.from)) {
+                (Some((_, victim)), Some((_, attacker))) => {
+                    -(victim.value() * 10 - attacker.value() / 10)
+                }
+                _ => 0,
+            }
+        });
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoardPos;
+
+    const MIDDLEGAME: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+
+    #[test]
+    fn single_thread_is_deterministic() {
+        let (board, side) = Board::from_fen(MIDDLEGAME).unwrap();
+        let search = Search::new(SearchOptions::default());
+        let first = search.go(&board, side, Limits::depth(3), Stop::new(), |_| {});
+
+        // a warm table would change the node count
+        search.clear();
+        let second = search.go(&board, side, Limits::depth(3), Stop::new(), |_| {});
+        assert_eq!(first, second);
+
+        let fresh = super::search(&board, side, Limits::depth(3), Stop::new(), |_| {});
+        assert_eq!(first, fresh);
+    }
+
+    /// every technique switched off on its own, then all of them
+    fn toggles() -> Vec<(&'static str, Techniques)> {
+        let mut toggles = Techniques::NAMES
+            .iter()
+            .map(|&name| {
+                let mut techniques = Techniques::ALL;
+                techniques.set(name, false);
+                (name, techniques)
+            })
+            .collect::<Vec<_>>();
+        toggles.push(("all", Techniques::ALL));
+        toggles.push(("none", Techniques::NONE));
+        toggles
+    }
+
+    fn search_with(fen: &str, techniques: Techniques, depth: u8) -> SearchResult {
+        let (board, side) = Board::from_fen(fen).unwrap();
+        let options = SearchOptions {
+            techniques,
+            ..Default::default()
+        };
+        Search::new(options).go(&board, side, Limits::depth(depth), Stop::new(), |_| {})
+    }
+
+    #[test]
+    fn toggles_find_back_rank_mate() {
+        let mate = Move {
+            from: BoardPos::parse("d1").unwrap(),
+            to: BoardPos::parse("d8").unwrap(),
+            promotion: None,
+        };
+        for (name, techniques) in toggles() {
+            let result = search_with("3r2k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", techniques, 4);
+            assert_eq!(result.best, Some(mate), "{name}");
+            assert_eq!(result.score, MATE - 1, "{name}");
+        }
+    }
+
+    #[test]
+    fn toggles_find_quiet_mate_in_two() {
+        // only a quiet rook move keeps the king on the back rank
+        for (name, techniques) in toggles() {
+            let result = search_with("k7/8/1K6/8/8/8/8/1R6 w - - 0 1", techniques, 5);
+            assert_eq!(result.score, MATE - 3, "{name}");
+
+            let (mut board, side) = Board::from_fen("k7/8/1K6/8/8/8/8/1R6 w - - 0 1").unwrap();
+            board.play(result.best.unwrap());
+            let replies = board.legal_moves(side.other());
+            assert!(!replies.is_empty(), "{name}");
+            // every reply runs into a mate in one
+            for reply in replies {
+                let mut board = board.clone();
+                board.play(reply);
+                let mates = board.legal_moves(side).into_iter().any(|mv| {
+                    let mut board = board.clone();
+                    board.play(mv);
+                    board.in_check(side.other()) && board.legal_moves(side.other()).is_empty()
+                });
+                assert!(mates, "{name} {reply}");
+            }
+        }
+    }
+
+    #[test]
+    fn toggles_find_promotion_mate() {
+        // promotions are never pruned or reduced as quiet moves
+        let promotion = Move {
+            from: BoardPos::parse("h7").unwrap(),
+            to: BoardPos::parse("h8").unwrap(),
+            promotion: Some(Piece::Queen),
+        };
+        for (name, techniques) in toggles() {
+            let result = search_with("k7/7P/1K6/8/8/8/8/8 w - - 0 1", techniques, 4);
+            assert_eq!(result.best, Some(promotion), "{name}");
+            assert_eq!(result.score, MATE - 1, "{name}");
+        }
+    }
+
+    #[test]
+    fn toggles_win_the_hanging_queen() {
+        let capture = Move {
+            from: BoardPos::parse("f3").unwrap(),
+            to: BoardPos::parse("d4").unwrap(),
+            promotion: None,
+        };
+        for (name, techniques) in toggles() {
+            let result = search_with("4k3/8/8/8/3q4/5N2/8/4K3 w - - 0 1", techniques, 3);
+            assert_eq!(result.best, Some(capture), "{name}");
+        }
+    }
+
+    #[test]
+    fn weaker_strengths_play_legal_moves() {
+        // lower ratings search shallower with more noise
+        let weakest = Strength::from_elo(crate::strength::MIN_ELO);
+        assert_eq!(weakest.max_depth, Some(1));
+        let mut last = weakest;
+        for elo in (500..crate::strength::MAX_ELO).step_by(100) {
+            let strength = Strength::from_elo(elo);
+            assert!(strength.max_depth >= last.max_depth, "{elo}");
+            assert!(strength.eval_noise <= last.eval_noise, "{elo}");
+            last = strength;
+        }
+        assert!(Strength::from_elo(crate::strength::MAX_ELO).is_full());
+
+        let (board, side) = Board::from_fen(MIDDLEGAME).unwrap();
+        let legal = board.legal_moves(side);
+        for strength in [Strength::RANDOM, weakest] {
+            let options = SearchOptions {
+                strength,
+                ..Default::default()
+            };
+            let result =
+                Search::new(options).go(&board, side, Limits::depth(6), Stop::new(), |_| {});
+            assert!(legal.contains(&result.best.unwrap()));
+            assert!(result.depth <= 1);
+        }
+    }
+}
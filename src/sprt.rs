@@ -0,0 +1,202 @@
+use core::fmt;
+
+//
+
+/// sequential probability ratio test between two elo hypotheses
+///
+/// h0 says the first engine is `elo0` stronger, h1 that it is `elo1`
+/// stronger, `alpha` and `beta` are the false positive and false negative
+/// rates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// game pair results, `counts[i]` pairs scored `i / 2` points out of 2
+///
+/// the two games of a pair share an opening with colours swapped, counting
+/// pairs instead of games cancels out most of the opening bias
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pentanomial {
+    pub counts: [u32; 5],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// h0, the change is not an improvement of `elo1`
+    AcceptH0,
+    /// h1, the change is not worse than `elo0`
+    AcceptH1,
+    Continue,
+}
+
+//
+
+impl Sprt {
+    /// `elo0,elo1[,alpha,beta]`, alpha and beta default to 0.05
+    pub fn parse(s: &str) -> Option<Self> {
+        let numbers = s
+            .split(',')
+            .map(|n| n.parse::<f64>().ok().filter(|n| n.is_finite()))
+            .collect::<Option<Vec<_>>>()?;
+        let (elo0, elo1, alpha, beta) = match numbers[..] {
+            [elo0, elo1] => (elo0, elo1, 0.05, 0.05),
+            [elo0, elo1, alpha, beta] => (elo0, elo1, alpha, beta),
+            _ => return None,
+        };
+        let rate = |rate: f64| rate > 0.0 && rate < 0.5;
+        (elo0 < elo1 && rate(alpha) && rate(beta)).then_some(Self {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        })
+    }
+
+    /// the llr accepts h0 at or below the first and h1 at or above the
+    /// second bound
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// log likelihood ratio of h1 against h0
+    ///
+    /// uses the normal approximation of the pair scores, 0 until the pairs
+    /// vary at all
+    pub fn llr(&self, pairs: &Pentanomial) -> f64 {
+        let n = pairs.pairs() as f64;
+        let (mean, variance) = pairs.mean_variance();
+        if n < 2.0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn decide(&self, llr: f64) -> Decision {
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Decision::AcceptH0
+        } else if llr >= upper {
+            Decision::AcceptH1
+        } else {
+            Decision::Continue
+        }
+    }
+}
+
+impl Pentanomial {
+    /// adds a pair from the points of its two games
+    pub fn add(&mut self, first: f64, second: f64) {
+        let i = ((first + second) * 2.0).round().clamp(0.0, 4.0) as usize;
+        self.counts[i] += 1;
+    }
+
+    pub fn pairs(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// mean and variance of the score per game of a pair
+    pub fn mean_variance(&self) -> (f64, f64) {
+        let n = self.pairs() as f64;
+        if n == 0.0 {
+            return (0.5, 0.0);
+        }
+        let score = |i: usize| i as f64 / 4.0;
+        let mean = (0..5)
+            .map(|i| self.counts[i] as f64 * score(i))
+            .sum::<f64>()
+            / n;
+        let variance = (0..5)
+            .map(|i| self.counts[i] as f64 * (score(i) - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        (mean, variance)
+    }
+}
+
+impl fmt::Display for Pentanomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e] = self.counts;
+        write!(f, "[{a}, {b}, {c}, {d}, {e}]")
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Decision::AcceptH0 => "h0 accepted",
+            Decision::AcceptH1 => "h1 accepted",
+            Decision::Continue => "inconclusive",
+        })
+    }
+}
+
+//
+
+/// score per game of the stronger side with logistic elo
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(counts: [u32; 5]) -> Pentanomial {
+        Pentanomial { counts }
+    }
+
+    #[test]
+    fn parses_and_bounds() {
+        let sprt = Sprt::parse("0,5").unwrap();
+        assert_eq!((sprt.alpha, sprt.beta), (0.05, 0.05));
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+
+        assert_eq!(Sprt::parse("0,5,0.1,0.2").map(|sprt| sprt.beta), Some(0.2));
+        assert_eq!(Sprt::parse("5,0"), None);
+        assert_eq!(Sprt::parse("0,5,0.5,0.05"), None);
+        assert_eq!(Sprt::parse("0,5,0.05"), None);
+    }
+
+    #[test]
+    fn pentanomial_counts() {
+        let mut pentanomial = Pentanomial::default();
+        pentanomial.add(1.0, 1.0);
+        pentanomial.add(0.5, 0.0);
+        pentanomial.add(0.5, 0.5);
+        pentanomial.add(1.0, 0.0);
+        assert_eq!(pentanomial.counts, [0, 1, 2, 0, 1]);
+        assert_eq!(pentanomial.to_string(), "[0, 1, 2, 0, 1]");
+        let (mean, _) = pentanomial.mean_variance();
+        assert!((mean - 0.5625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn decides_both_ways() {
+        let sprt = Sprt::parse("0,10").unwrap();
+        assert_eq!(sprt.llr(&Pentanomial::default()), 0.0);
+        // every pair drawn gives nothing to decide on
+        assert_eq!(sprt.llr(&pairs([0, 0, 50, 0, 0])), 0.0);
+
+        // an even score leans to h0 but is far from a decision
+        let even = pairs([10, 20, 40, 20, 10]);
+        assert!(sprt.llr(&even) < 0.0);
+        assert_eq!(sprt.decide(sprt.llr(&even)), Decision::Continue);
+
+        let winning = pairs([100, 300, 800, 500, 300]);
+        assert_eq!(sprt.decide(sprt.llr(&winning)), Decision::AcceptH1);
+        let losing = pairs([300, 500, 800, 300, 100]);
+        assert_eq!(sprt.decide(sprt.llr(&losing)), Decision::AcceptH0);
+    }
+}
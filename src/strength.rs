@@ -0,0 +1,115 @@
+use core::fmt;
+
+//
+
+/// limits on how well the search plays, the default is full strength
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Strength {
+    /// plays any legal move without searching
+    pub random: bool,
+    /// iterative deepening stops after this depth whatever the limits say
+    pub max_depth: Option<u8>,
+    /// evaluations are off by up to this many centipawns either way
+    pub eval_noise: i32,
+}
+
+/// preset strengths for the gui opponent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Difficulty {
+    Random,
+    Weak,
+    Intermediate,
+    #[default]
+    Full,
+}
+
+//
+
+/// weakest `UCI_Elo`, searches 1 ply with a lot of noise
+pub const MIN_ELO: u32 = 400;
+
+/// `UCI_Elo` that plays at full strength
+pub const MAX_ELO: u32 = 2400;
+
+//
+
+impl Strength {
+    pub const FULL: Self = Self {
+        random: false,
+        max_depth: None,
+        eval_noise: 0,
+    };
+
+    pub const RANDOM: Self = Self {
+        random: true,
+        max_depth: None,
+        eval_noise: 0,
+    };
+
+    /// rough mapping of a rating to a depth limit and eval noise, both
+    /// shrink linearly from [`MIN_ELO`] up to [`MAX_ELO`]
+    ///
+    /// not calibrated against anything, higher is just stronger
+    pub fn from_elo(elo: u32) -> Self {
+        if elo >= MAX_ELO {
+            return Self::FULL;
+        }
+        let t = elo.saturating_sub(MIN_ELO) as f32 / (MAX_ELO - MIN_ELO) as f32;
+        Self {
+            random: false,
+            max_depth: Some(1 + (t * 7.0) as u8),
+            eval_noise: ((1.0 - t) * 400.0) as i32,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        *self == Self::FULL
+    }
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 4] = [
+        Difficulty::Random,
+        Difficulty::Weak,
+        Difficulty::Intermediate,
+        Difficulty::Full,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Random => "random",
+            Difficulty::Weak => "weak",
+            Difficulty::Intermediate => "intermediate",
+            Difficulty::Full => "full",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(s))
+    }
+
+    /// the `UCI_Elo` the level plays like, `None` for random moves
+    pub fn elo(self) -> Option<u32> {
+        match self {
+            Difficulty::Random => None,
+            Difficulty::Weak => Some(1000),
+            Difficulty::Intermediate => Some(1800),
+            Difficulty::Full => Some(MAX_ELO),
+        }
+    }
+
+    pub fn strength(self) -> Strength {
+        match self.elo() {
+            Some(elo) => Strength::from_elo(elo),
+            None => Strength::RANDOM,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
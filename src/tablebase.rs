@@ -0,0 +1,288 @@
+use crate::{
+    board::Move,
+    eval::TB_WIN,
+    piece::{Piece, Side},
+    Board,
+};
+use core::fmt;
+use shakmaty::{Bitboard, CastlingMode, Chess, Color, FromSetup, Role, Setup, Square};
+use shakmaty_syzygy::{AmbiguousWdl, Tablebase as Syzygy};
+use std::{io, path::Path};
+
+//
+
+/// syzygy wdl and dtz tables from local directories
+///
+/// results follow the 50 move rule, cursed wins and blessed losses are draws
+pub struct Tablebase {
+    syzygy: Syzygy<Chess>,
+}
+
+/// outcome with perfect play from the point of view of the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wdl {
+    Loss,
+    Draw,
+    Win,
+}
+
+//
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Self {
+            syzygy: Syzygy::new(),
+        }
+    }
+
+    /// tablebase with every table in `dir`
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut tb = Self::new();
+        tb.add_directory(dir)?;
+        Ok(tb)
+    }
+
+    /// adds the `.rtbw` and `.rtbz` files of `dir`, returns how many
+    pub fn add_directory(&mut self, dir: impl AsRef<Path>) -> io::Result<usize> {
+        self.syzygy.add_directory(dir)
+    }
+
+    /// most pieces in any added table, 0 without tables
+    pub fn max_pieces(&self) -> usize {
+        self.syzygy.max_pieces()
+    }
+
+    /// cheap check that the position could be in the tables, the tables
+    /// have no castling
+    pub fn can_probe(&self, board: &Board) -> bool {
+        board.castling() == [false; 4] && board.iter().count() <= self.max_pieces()
+    }
+
+    /// win, draw or loss for `side` to move counting the halfmove clock of
+    /// the board, `None` if it is not in the tables or dtz rounding leaves it
+    /// open
+    ///
+    /// right after a capture or pawn move the wdl tables are enough, later
+    /// the dtz tables are needed too
+    pub fn probe_wdl(&self, board: &Board, side: Side) -> Option<Wdl> {
+        let pos = self.position(board, side)?;
+        let wdl = if board.halfmove() == 0 {
+            self.syzygy
+                .probe_wdl_after_zeroing(&pos)
+                .map(AmbiguousWdl::from)
+        } else {
+            self.syzygy.probe_wdl(&pos)
+        };
+        match wdl {
+            Ok(wdl) => from_syzygy(wdl),
+            Err(err) => {
+                log::debug!("wdl probe failed: {err}");
+                None
+            }
+        }
+    }
+
+    /// plies to the next capture (or mate) with perfect play, positive when
+    /// `side` to move wins and negative when it loses, 0 for draws
+    ///
+    /// needs the dtz tables, the value may be off by one ply
+    pub fn probe_dtz(&self, board: &Board, side: Side) -> Option<i32> {
+        let pos = self.position(board, side)?;
+        match self.syzygy.probe_dtz(&pos) {
+            Ok(dtz) => Some(dtz.ignore_rounding().0),
+            Err(err) => {
+                log::debug!("dtz probe failed: {err}");
+                None
+            }
+        }
+    }
+
+    /// the move that keeps the best outcome and makes progress towards it
+    pub fn best_move(&self, board: &Board, side: Side) -> Option<(Move, Wdl)> {
+        let pos = self.position(board, side)?;
+        let (mv, _) = match self.syzygy.best_move(&pos) {
+            Ok(best) => best?,
+            Err(err) => {
+                log::debug!("root probe failed: {err}");
+                return None;
+            }
+        };
+        // castling is the king taking its rook in shakmaty
+        let mv = Move::parse(&mv.to_uci(CastlingMode::Standard).to_string())?;
+
+        let mut board = board.clone();
+        board.play(mv);
+        let wdl = self.probe_wdl(&board, side.other())?.other();
+        Some((mv, wdl))
+    }
+
+    fn position(&self, board: &Board, side: Side) -> Option<Chess> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        to_chess(board, side)
+    }
+}
+
+/// the outcome under the 50 move rule
+fn from_syzygy(wdl: AmbiguousWdl) -> Option<Wdl> {
+    match wdl {
+        AmbiguousWdl::Loss => Some(Wdl::Loss),
+        AmbiguousWdl::BlessedLoss | AmbiguousWdl::Draw | AmbiguousWdl::CursedWin => Some(Wdl::Draw),
+        AmbiguousWdl::Win => Some(Wdl::Win),
+        AmbiguousWdl::MaybeLoss | AmbiguousWdl::MaybeWin => None,
+    }
+}
+
+/// the position for the prober, built square by square without a fen
+fn to_chess(board: &Board, side: Side) -> Option<Chess> {
+    let color = |side| match side {
+        Side::White => Color::White,
+        Side::Black => Color::Black,
+    };
+    let mut setup = Setup::empty();
+    for (side, piece, pos) in board.iter() {
+        let role = match piece {
+            Piece::Pawn => Role::Pawn,
+            Piece::Knight => Role::Knight,
+            Piece::Bishop => Role::Bishop,
+            Piece::Rook => Role::Rook,
+            Piece::Queen => Role::Queen,
+            Piece::King => Role::King,
+        };
+        let square = Square::new(pos.to_usize() as u32);
+        setup.board.set_piece_at(square, role.of(color(side)));
+    }
+    setup.turn = color(side);
+    setup.castling_rights = [
+        (0, Square::H1),
+        (1, Square::A1),
+        (2, Square::H8),
+        (3, Square::A8),
+    ]
+    .into_iter()
+    .filter(|&(i, _)| board.castling()[i])
+    .fold(Bitboard::EMPTY, |rooks, (_, square)| rooks.with(square));
+    setup.ep_square = board
+        .en_passant()
+        .map(|pos| Square::new(pos.to_usize() as u32));
+    setup.halfmoves = board.halfmove();
+    Chess::from_setup(setup, CastlingMode::Standard).ok()
+}
+
+impl Default for Tablebase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wdl {
+    /// the same outcome for the opponent
+    pub const fn other(self) -> Self {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+
+    /// search score `ply` plies from the root, sooner wins score higher
+    pub const fn score(self, ply: u8) -> i32 {
+        match self {
+            Wdl::Loss => -TB_WIN + ply as i32,
+            Wdl::Draw => 0,
+            Wdl::Win => TB_WIN - ply as i32,
+        }
+    }
+}
+
+impl fmt::Display for Wdl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Wdl::Loss => "loss",
+            Wdl::Draw => "draw",
+            Wdl::Win => "win",
+        })
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{fen::Fen, EnPassantMode};
+
+    #[test]
+    fn conversion_matches_fen() {
+        for fen in [
+            "8/8/8/4k3/8/8/2Q5/4K3 b - - 0 1",
+            "8/8/3k4/8/8/8/1R6/4K3 w - - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            "r3k2r/8/8/8/3pP3/8/8/R3K2R b KQkq e3 5 1",
+        ] {
+            let (board, side) = Board::from_fen(fen).unwrap();
+            let pos = to_chess(&board, side).unwrap();
+            let back = Fen::from_position(&pos, EnPassantMode::Legal).to_string();
+            assert_eq!(back, fen);
+        }
+        // two kings of one side are no position at all
+        let (board, side) = Board::from_fen("8/8/8/4k3/8/8/8/K3K3 w - - 0 1").unwrap();
+        assert!(to_chess(&board, side).is_none());
+    }
+
+    #[test]
+    fn empty_tablebase_probes_nothing() {
+        let tablebase = Tablebase::new();
+        let (board, side) = Board::from_fen("8/8/8/4k3/8/8/2Q5/4K3 b - - 0 1").unwrap();
+        assert!(!tablebase.can_probe(&board));
+        assert_eq!(tablebase.probe_wdl(&board, side), None);
+    }
+
+    #[test]
+    fn fifty_move_rule_outcomes() {
+        assert_eq!(from_syzygy(AmbiguousWdl::Win), Some(Wdl::Win));
+        assert_eq!(from_syzygy(AmbiguousWdl::CursedWin), Some(Wdl::Draw));
+        assert_eq!(from_syzygy(AmbiguousWdl::BlessedLoss), Some(Wdl::Draw));
+        assert_eq!(from_syzygy(AmbiguousWdl::Loss), Some(Wdl::Loss));
+        assert_eq!(from_syzygy(AmbiguousWdl::MaybeWin), None);
+    }
+
+    /// `wget -i SOURCE.txt` in `res/syzygy` fetches the tables
+    #[test]
+    fn probes_small_tables() {
+        let dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/res/syzygy"));
+        let tables = ["KQvK.rtbw", "KQvK.rtbz", "KRvK.rtbw", "KRvK.rtbz"];
+        if let Some(table) = tables.iter().find(|table| !dir.join(table).exists()) {
+            eprintln!("skipping, {table} is missing from {}", dir.display());
+            return;
+        }
+
+        let tablebase = Tablebase::open(dir).unwrap();
+        let probe = |fen: &str| {
+            let (board, side) = Board::from_fen(fen).unwrap();
+            tablebase.probe_wdl(&board, side)
+        };
+
+        assert_eq!(probe("8/8/8/4k3/8/8/2Q5/4K3 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/2Q5/4K3 b - - 0 1"), Some(Wdl::Loss));
+        // stalemate
+        assert_eq!(probe("k7/8/1Q6/8/8/8/8/4K3 b - - 0 1"), Some(Wdl::Draw));
+
+        // the rook cannot mate in the plies the 50 move rule leaves
+        assert_eq!(probe("8/8/8/4k3/8/8/8/R3K3 w - - 0 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/R3K3 w - - 10 1"), Some(Wdl::Win));
+        assert_eq!(probe("8/8/8/4k3/8/8/8/R3K3 w - - 96 1"), Some(Wdl::Draw));
+
+        let (board, side) = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let (mv, wdl) = tablebase.best_move(&board, side).unwrap();
+        assert!(board.legal_moves(side).contains(&mv));
+        assert_eq!(wdl, Wdl::Win);
+
+        // pawns are probed now, castling rights never are
+        let (board, _) = Board::from_fen("8/8/8/4k3/8/8/4P3/4K3 w - - 0 1").unwrap();
+        assert!(tablebase.can_probe(&board));
+        let (board, _) = Board::from_fen("8/8/8/4k3/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(!tablebase.can_probe(&board));
+    }
+}
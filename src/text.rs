@@ -0,0 +1,68 @@
+use font8x8::legacy::BASIC_LEGACY;
+use srs2dge::{
+    batch::{quad::QuadMesh, BatchRenderer, Idx},
+    glam::{Vec2, Vec4},
+    packer::TexturePosition,
+    program::DefaultVertex,
+};
+
+//
+
+/// a line of text drawn with one colored quad per lit font pixel
+#[derive(Debug, Default)]
+pub struct Text {
+    quads: Vec<Idx>,
+    used: usize,
+}
+
+//
+
+impl Text {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// replaces the text, `pos` is the top left corner and `height` the
+    /// height of one character
+    pub fn set(
+        &mut self,
+        batcher: &mut BatchRenderer<DefaultVertex, QuadMesh>,
+        text: &str,
+        pos: Vec2,
+        height: f32,
+        col: Vec4,
+    ) {
+        let pixel = height / 8.0;
+        let mut used = 0;
+        for (i, c) in text.chars().enumerate() {
+            let glyph = BASIC_LEGACY
+                .get(c as usize)
+                .unwrap_or(&BASIC_LEGACY[b'?' as usize]);
+            for (y, row) in glyph.iter().enumerate() {
+                for x in (0..8).filter(|x| row >> x & 1 == 1) {
+                    let quad = QuadMesh {
+                        pos: pos + Vec2::new((i * 8 + x) as f32, y as f32) * pixel,
+                        size: Vec2::new(pixel, pixel),
+                        col,
+                        tex: TexturePosition::default(),
+                    };
+                    match self.quads.get(used) {
+                        Some(&idx) => *batcher.get_mut(idx) = quad,
+                        None => self.quads.push(batcher.push_with(quad)),
+                    }
+                    used += 1;
+                }
+            }
+        }
+
+        // hide what the previous text used on top
+        for &idx in self.quads[used..self.used.max(used)].iter() {
+            batcher.get_mut(idx).col.w = 0.0;
+        }
+        self.used = used;
+    }
+
+    pub fn clear(&mut self, batcher: &mut BatchRenderer<DefaultVertex, QuadMesh>) {
+        self.set(batcher, "", Vec2::ZERO, 0.0, Vec4::ZERO);
+    }
+}
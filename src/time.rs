@@ -0,0 +1,371 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+//
+
+/// what the search is allowed to spend on a single move
+///
+/// a limit left as `None` is not enforced, `Limits::default()` searches until
+/// stopped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// time left on the clock of the side to move
+    pub time: Option<Duration>,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+    /// fixed time for this move, overrides the clock
+    pub move_time: Option<Duration>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+}
+
+/// shared stop signal, cloning it gives another handle to the same flag
+#[derive(Debug, Clone, Default)]
+pub struct Stop(Arc<AtomicBool>);
+
+/// set while searching on the opponent's time, a ponder hit clears it and
+/// starts the clock
+#[derive(Debug, Clone, Default)]
+pub struct Pondering(Arc<AtomicBool>);
+
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    limits: Limits,
+    stop: Stop,
+    pondering: Pondering,
+    /// when the clock started, moved to the ponder hit
+    start: Instant,
+    was_pondering: bool,
+
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+
+    best_move_changes: f32,
+    score_drop: i32,
+}
+
+//
+
+/// kept in reserve for the gui/protocol round trip
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+/// assumed number of moves left when the time control does not say
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+//
+
+impl Limits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> Self {
+        Self {
+            move_time: Some(move_time),
+            ..Default::default()
+        }
+    }
+
+    pub fn clock(time: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        Self {
+            time: Some(time),
+            increment,
+            moves_to_go,
+            ..Default::default()
+        }
+    }
+
+    /// only depth and node limits, the search result does not depend on the
+    /// machine
+    pub fn is_deterministic(&self) -> bool {
+        self.time.is_none()
+            && self.move_time.is_none()
+            && (self.depth.is_some() || self.nodes.is_some())
+    }
+}
+
+impl Stop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Pondering {
+    pub fn new(pondering: bool) -> Self {
+        Self(Arc::new(AtomicBool::new(pondering)))
+    }
+
+    /// the opponent played the expected move
+    pub fn hit(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl TimeManager {
+    pub fn new(limits: Limits, stop: Stop) -> Self {
+        Self::with_pondering(limits, stop, Pondering::default())
+    }
+
+    /// limits only apply after the ponder hit
+    pub fn with_pondering(limits: Limits, stop: Stop, pondering: Pondering) -> Self {
+        let (soft, hard) = Self::allot(&limits);
+        log::debug!("time allotted soft: {soft:?} hard: {hard:?}");
+
+        Self {
+            limits,
+            stop,
+            was_pondering: pondering.is_pondering(),
+            pondering,
+            start: Instant::now(),
+
+            soft,
+            hard,
+
+            best_move_changes: 0.0,
+            score_drop: 0,
+        }
+    }
+
+    /// soft and hard limits for a single move
+    ///
+    /// the soft limit is where a new iteration is not started anymore, the
+    /// hard limit aborts the iteration in progress
+    fn allot(limits: &Limits) -> (Option<Duration>, Option<Duration>) {
+        if let Some(move_time) = limits.move_time {
+            let time = move_time
+                .saturating_sub(MOVE_OVERHEAD)
+                .max(Duration::from_millis(1));
+            return (Some(time), Some(time));
+        }
+
+        let time = match limits.time {
+            Some(time) => time.saturating_sub(MOVE_OVERHEAD),
+            None => return (None, None),
+        };
+
+        let moves_to_go = limits
+            .moves_to_go
+            .unwrap_or(DEFAULT_MOVES_TO_GO)
+            .clamp(1, 50);
+
+        // never plan to use more than what is left on the clock
+        let base = time / moves_to_go + limits.increment * 3 / 4;
+        let base = base.min(time / 2);
+
+        let soft = base * 6 / 10;
+        let hard = (base * 3).min(time * 8 / 10);
+
+        let min = Duration::from_millis(1);
+        (Some(soft.max(min)), Some(hard.max(min)))
+    }
+
+    pub fn stop_handle(&self) -> Stop {
+        self.stop.clone()
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// checked inside the search, `true` means abort right now
+    pub fn should_stop(&mut self, nodes: u64) -> bool {
+        if self.stop.is_stopped() {
+            return true;
+        }
+
+        if self.pondering() {
+            return false;
+        }
+
+        if matches!(self.limits.nodes, Some(limit) if nodes >= limit) {
+            return true;
+        }
+
+        // reading the clock is not free
+        if nodes & 1023 == 0 {
+            if let Some(hard) = self.hard {
+                if self.elapsed() >= hard {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// checked between iterative deepening iterations
+    pub fn should_start(&mut self, depth: u8) -> bool {
+        if self.stop.is_stopped() {
+            return false;
+        }
+
+        if matches!(self.limits.depth, Some(limit) if depth > limit) {
+            return false;
+        }
+
+        if self.pondering() {
+            return true;
+        }
+
+        match self.soft {
+            Some(soft) => self.elapsed() < self.scaled_soft(soft),
+            None => true,
+        }
+    }
+
+    /// feeds back the result of a finished iteration
+    ///
+    /// a changing best move or a dropping score buys more time, a stable
+    /// best move lets the search finish early
+    pub fn update(&mut self, best_move_changed: bool, score_drop: i32) {
+        self.best_move_changes *= 0.5;
+        if best_move_changed {
+            self.best_move_changes += 1.0;
+        }
+        self.score_drop = score_drop.max(0);
+    }
+
+    /// restarts the clock on the first call after the ponder hit
+    fn pondering(&mut self) -> bool {
+        if !self.was_pondering {
+            return false;
+        }
+
+        if self.pondering.is_pondering() {
+            true
+        } else {
+            log::debug!("ponder hit after {:?}", self.start.elapsed());
+            self.was_pondering = false;
+            self.start = Instant::now();
+            false
+        }
+    }
+
+    fn scaled_soft(&self, soft: Duration) -> Duration {
+        let instability = 0.7 + self.best_move_changes * 0.6;
+        let falling = 1.0 + (self.score_drop as f32 / 100.0).min(1.0);
+
+        let scaled = soft.mul_f32(instability * falling);
+        match self.hard {
+            Some(hard) => scaled.min(hard),
+            None => scaled,
+        }
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search::search, Board};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn allot_move_time() {
+        let (soft, hard) = TimeManager::allot(&Limits::move_time(ms(1000)));
+        assert_eq!(soft, Some(ms(970)));
+        assert_eq!(hard, Some(ms(970)));
+
+        assert_eq!(TimeManager::allot(&Limits::depth(3)), (None, None));
+    }
+
+    #[test]
+    fn allot_increment() {
+        // 30s / 30 moves + 3/4 of the increment
+        let (soft, hard) = TimeManager::allot(&Limits::clock(ms(30_030), ms(2000), None));
+        assert_eq!(soft, Some(ms(1500)));
+        assert_eq!(hard, Some(ms(7500)));
+
+        let (plain, _) = TimeManager::allot(&Limits::clock(ms(30_030), ms(0), None));
+        assert_eq!(plain, Some(ms(600)));
+    }
+
+    #[test]
+    fn allot_moves_to_go() {
+        // the last move before the time control gets at most half the clock
+        let (soft, hard) = TimeManager::allot(&Limits::clock(ms(10_030), ms(0), Some(1)));
+        assert_eq!(soft, Some(ms(3000)));
+        assert_eq!(hard, Some(ms(8000)));
+
+        let (soft, hard) = TimeManager::allot(&Limits::clock(ms(10_030), ms(0), Some(10)));
+        assert_eq!(soft, Some(ms(600)));
+        assert_eq!(hard, Some(ms(3000)));
+    }
+
+    #[test]
+    fn stability_scaling() {
+        let limits = Limits::clock(ms(30_030), ms(0), None);
+        let mut time = TimeManager::new(limits, Stop::new());
+        let soft = ms(1000);
+
+        // a stable best move finishes early
+        time.update(false, 0);
+        let stable = time.scaled_soft(soft);
+        assert!(stable < soft);
+
+        time.update(true, 0);
+        time.update(true, 0);
+        let unstable = time.scaled_soft(soft);
+        assert!(unstable > soft);
+
+        time.update(true, 100);
+        assert!(time.scaled_soft(soft) > unstable);
+
+        // never past the hard limit
+        assert_eq!(time.scaled_soft(ms(60_000)), time.hard.unwrap());
+    }
+
+    #[test]
+    fn deterministic_limits() {
+        let (board, side) =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        for limits in [Limits::depth(3), Limits::nodes(5_000)] {
+            assert!(limits.is_deterministic());
+            let first = search(&board, side, limits, Stop::new(), |_| {});
+            let second = search(&board, side, limits, Stop::new(), |_| {});
+            assert!(first.best.is_some());
+            assert_eq!(first.best, second.best);
+            assert_eq!(first.nodes, second.nodes);
+        }
+    }
+}
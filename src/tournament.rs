@@ -0,0 +1,1061 @@
+use crate::{
+    board::Move,
+    pgn::{self, Game, GameResult},
+    piece::{Piece, Side},
+    search::{Search, SearchOptions},
+    sprt::{Decision, Pentanomial, Sprt},
+    strength::Strength,
+    time::{Limits, Stop},
+    uci_client::UciEngine,
+    Board,
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+//
+
+/// one side of a match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contender {
+    pub name: String,
+    pub engine: EngineConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineConfig {
+    /// the built in search with its own options
+    Builtin(SearchOptions),
+    /// path to a uci engine executable
+    External(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    /// `time` for `moves` moves (or the whole game) plus `increment` per move
+    Clock {
+        time: Duration,
+        increment: Duration,
+        moves: Option<u32>,
+    },
+    /// the same limits for every move without a clock
+    Fixed(Limits),
+}
+
+/// rules that end games early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Adjudication {
+    /// draw once each side made this many moves
+    pub max_moves: Option<u32>,
+    pub draw: Option<DrawRule>,
+    pub resign: Option<ResignRule>,
+}
+
+/// draw when both engines stay within `score` centipawns of 0 for `moves`
+/// moves each, starting from `move_number`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRule {
+    pub move_number: u32,
+    pub moves: u32,
+    pub score: i32,
+}
+
+/// loss for an engine that scores itself `score` centipawns or more behind
+/// for `moves` moves in a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResignRule {
+    pub moves: u32,
+    pub score: i32,
+}
+
+/// start position and moves played from it before the engines take over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub board: Board,
+    pub side: Side,
+    pub moves: Vec<Move>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchConfig {
+    pub contenders: [Contender; 2],
+    /// games are played in pairs, every opening once with each colour
+    pub games: usize,
+    /// games played at the same time
+    pub concurrency: usize,
+    pub time_control: TimeControl,
+    /// the starting position is used without any
+    pub openings: Vec<Opening>,
+    pub adjudication: Adjudication,
+}
+
+/// a finished game of a match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    /// 0 based, games `2n` and `2n + 1` share an opening
+    pub index: usize,
+    /// the contender that played white
+    pub white: usize,
+    pub game: Game,
+    /// why the game ended
+    pub reason: String,
+}
+
+/// wins, draws and losses of the first contender
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// matches up the two games of every opening for the sprt
+#[derive(Debug, Default)]
+struct Pairing {
+    /// points of the first finished game of every pair, `None` if it has no
+    /// result
+    halves: HashMap<usize, Option<f64>>,
+}
+
+enum Player {
+    Builtin(Search),
+    External(UciEngine),
+}
+
+//
+
+/// how much longer than its clock an engine may take before it is stopped
+const TIME_GRACE: Duration = Duration::from_secs(1);
+
+//
+
+/// plays the match, `on_game` sees every game as it finishes with the score
+/// so far and can end the match early by returning `false`
+///
+/// games finish out of order when several run at the same time
+pub fn play_match(
+    config: &MatchConfig,
+    mut on_game: impl FnMut(&GameRecord, &Score) -> bool,
+) -> io::Result<Score> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, records) = mpsc::channel();
+
+    thread::scope(|s| {
+        let mut workers = Vec::new();
+        for id in 0..config.concurrency.clamp(1, config.games.max(1)) {
+            let sender = sender.clone();
+            let (next, stop) = (&next, &stop);
+            let worker = thread::Builder::new()
+                .name(format!("match worker {id}"))
+                .spawn_scoped(s, move || -> io::Result<()> {
+                    let mut players = [
+                        Player::new(&config.contenders[0].engine)?,
+                        Player::new(&config.contenders[1].engine)?,
+                    ];
+                    while !stop.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= config.games {
+                            break;
+                        }
+                        let record = play_game(config, index, &mut players);
+                        if sender.send(record).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(())
+                })?;
+            workers.push(worker);
+        }
+        drop(sender);
+
+        let mut score = Score::default();
+        for record in records {
+            score.add(&record);
+            if !on_game(&record, &score) {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        for worker in workers {
+            worker.join().expect("match worker panicked")?;
+        }
+        Ok(score)
+    })
+}
+
+/// openings from an epd file (one position per line) or a pgn file (the
+/// moves of every game)
+pub fn load_openings(path: impl AsRef<Path>) -> io::Result<Vec<Opening>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"))
+    {
+        return Ok(pgn::parse(&text)
+            .into_iter()
+            .map(|game| Opening {
+                board: game.board,
+                side: game.side,
+                moves: game.moves,
+            })
+            .collect());
+    }
+
+    let mut openings = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        // epd has the first four fen fields and then operations
+        let fen = line
+            .split_whitespace()
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ");
+        match Board::from_fen(&fen) {
+            Ok((board, side)) => openings.push(Opening {
+                board,
+                side,
+                moves: Vec::new(),
+            }),
+            Err(err) => log::warn!("skipping opening '{line}': {err}"),
+        }
+    }
+    Ok(openings)
+}
+
+/// `match <engine> <engine> [games=N] [concurrency=N] [tc=[moves/]secs[+inc]]
+/// [st=secs] [depth=N] [nodes=N] [openings=file] [pgn=file] [maxmoves=N]
+/// [draw=movenumber,moves,cp] [resign=moves,cp] [sprt=elo0,elo1[,alpha,beta]]`
+///
+/// an engine is the path to a uci executable or `builtin`, optionally with
+/// search options like `builtin:Threads=2,LMR=false,Elo=1500`
+///
+/// with `sprt` the match stops as soon as the test accepts either
+/// hypothesis, `games` is then only the upper limit
+pub fn run(args: impl Iterator<Item = String>) -> io::Result<()> {
+    let usage = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: match <engine> <engine> [games=N] [concurrency=N] [tc=[moves/]secs[+inc]] \
+             [st=secs] [depth=N] [nodes=N] [openings=file] [pgn=file] [maxmoves=N] \
+             [draw=movenumber,moves,cp] [resign=moves,cp] [sprt=elo0,elo1[,alpha,beta]]",
+        )
+    };
+    let invalid = |arg: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid argument '{arg}'"),
+        )
+    };
+
+    let mut contenders = Vec::new();
+    let mut config = MatchConfig {
+        contenders: [Contender::builtin(), Contender::builtin()],
+        games: 0,
+        concurrency: 1,
+        time_control: TimeControl::Fixed(Limits::move_time(Duration::from_millis(100))),
+        openings: Vec::new(),
+        adjudication: Adjudication::default(),
+    };
+    let mut pgn_path = None;
+    let mut sprt = None;
+
+    for arg in args {
+        let (key, value) = match arg.split_once('=') {
+            Some(kv) if !kv.0.contains(':') => kv,
+            _ => {
+                contenders.push(Contender::parse(&arg).ok_or_else(|| invalid(&arg))?);
+                continue;
+            }
+        };
+        let ok = match key {
+            "games" => value.parse().map(|games| config.games = games).is_ok(),
+            "concurrency" => value.parse().map(|n| config.concurrency = n).is_ok(),
+            "tc" => TimeControl::parse(value)
+                .map(|tc| config.time_control = tc)
+                .is_some(),
+            "st" => seconds(value)
+                .map(|time| config.time_control = TimeControl::Fixed(Limits::move_time(time)))
+                .is_some(),
+            "depth" => value
+                .parse()
+                .map(|depth| config.time_control = TimeControl::Fixed(Limits::depth(depth)))
+                .is_ok(),
+            "nodes" => value
+                .parse()
+                .map(|nodes| config.time_control = TimeControl::Fixed(Limits::nodes(nodes)))
+                .is_ok(),
+            "openings" => {
+                config.openings = load_openings(value)?;
+                true
+            }
+            "pgn" => {
+                pgn_path = Some(value.to_string());
+                true
+            }
+            "maxmoves" => value
+                .parse()
+                .map(|moves| config.adjudication.max_moves = Some(moves))
+                .is_ok(),
+            "draw" => DrawRule::parse(value)
+                .map(|rule| config.adjudication.draw = Some(rule))
+                .is_some(),
+            "resign" => ResignRule::parse(value)
+                .map(|rule| config.adjudication.resign = Some(rule))
+                .is_some(),
+            "sprt" => Sprt::parse(value).map(|test| sprt = Some(test)).is_some(),
+            _ => false,
+        };
+        if !ok {
+            return Err(invalid(&arg));
+        }
+    }
+
+    let [first, second]: [Contender; 2] = contenders.try_into().map_err(|_| usage())?;
+    config.contenders = [first, second];
+    if config.games == 0 {
+        config.games = if sprt.is_some() { 20_000 } else { 10 };
+    }
+    if config.contenders[0].name == config.contenders[1].name {
+        config.contenders[1].name += " (2)";
+    }
+
+    let mut pgn = match pgn_path {
+        Some(path) => Some(BufWriter::new(File::create(path)?)),
+        None => None,
+    };
+
+    let mut pairing = Pairing::default();
+    let mut pairs = Pentanomial::default();
+    let mut decision = Decision::Continue;
+
+    let score = play_match(&config, |record, score| {
+        let [white, black] = record.colours().map(|i| &config.contenders[i].name);
+        println!(
+            "game {}/{}: {white} - {black} {} ({}), score {} - {} - {}",
+            record.index + 1,
+            config.games,
+            record.game.result,
+            record.reason,
+            score.wins,
+            score.draws,
+            score.losses,
+        );
+        if let Some(pgn) = pgn.as_mut() {
+            if let Err(err) = writeln!(pgn, "{}", record.game) {
+                log::error!("could not write the pgn: {err}");
+            }
+        }
+
+        let Some(sprt) = sprt else {
+            return true;
+        };
+        if decision != Decision::Continue {
+            // games that were already running when the test ended
+            return false;
+        }
+        let Some((first, second)) = pairing.add(record) else {
+            return true;
+        };
+        pairs.add(first, second);
+        let llr = sprt.llr(&pairs);
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "pairs {} {pairs}, llr {llr:.2} ({lower:.2}, {upper:.2})",
+            pairs.pairs()
+        );
+        decision = sprt.decide(llr);
+        decision == Decision::Continue
+    })?;
+
+    if let Some(mut pgn) = pgn {
+        pgn.flush()?;
+    }
+
+    println!();
+    print!("{}", score.table(&config.contenders));
+    if let Some(sprt) = sprt {
+        println!(
+            "sprt [{}, {}] alpha {} beta {}: {decision}, llr {:.2}",
+            sprt.elo0,
+            sprt.elo1,
+            sprt.alpha,
+            sprt.beta,
+            sprt.llr(&pairs)
+        );
+    }
+    Ok(())
+}
+
+impl Contender {
+    /// the built in search at full strength
+    pub fn builtin() -> Self {
+        Self {
+            name: "builtin".to_string(),
+            engine: EngineConfig::Builtin(SearchOptions::default()),
+        }
+    }
+
+    /// `builtin[:Name=value,...]` or a path
+    pub fn parse(s: &str) -> Option<Self> {
+        let options = match s.strip_prefix("builtin") {
+            Some("") => "",
+            Some(options) => options.strip_prefix(':')?,
+            None => {
+                let name = Path::new(s).file_name()?.to_string_lossy().into_owned();
+                return Some(Self {
+                    name,
+                    engine: EngineConfig::External(s.to_string()),
+                });
+            }
+        };
+
+        let mut search = SearchOptions::default();
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (name, value) = option.split_once('=')?;
+            match name {
+                "Threads" => search.threads = value.parse().ok()?,
+                "Hash" => search.hash = value.parse().ok()?,
+                "Elo" => search.strength = Strength::from_elo(value.parse().ok()?),
+                _ => {
+                    if !search.techniques.set(name, value.parse().ok()?) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(Self {
+            name: s.to_string(),
+            engine: EngineConfig::Builtin(search),
+        })
+    }
+}
+
+impl TimeControl {
+    /// `[moves/]seconds[+increment]` like `40/60+0.5`
+    pub fn parse(s: &str) -> Option<Self> {
+        let (moves, rest) = match s.split_once('/') {
+            Some((moves, rest)) => (Some(moves.parse().ok()?), rest),
+            None => (None, s),
+        };
+        let (time, increment) = match rest.split_once('+') {
+            Some((time, increment)) => (seconds(time)?, seconds(increment)?),
+            None => (seconds(rest)?, Duration::ZERO),
+        };
+        Some(TimeControl::Clock {
+            time,
+            increment,
+            moves,
+        })
+    }
+}
+
+impl DrawRule {
+    /// `movenumber,moves,cp`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut numbers = s.split(',');
+        let rule = Self {
+            move_number: numbers.next()?.parse().ok()?,
+            moves: numbers.next()?.parse().ok()?,
+            score: numbers.next()?.parse().ok()?,
+        };
+        numbers.next().is_none().then_some(rule)
+    }
+}
+
+impl ResignRule {
+    /// `moves,cp`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut numbers = s.split(',');
+        let rule = Self {
+            moves: numbers.next()?.parse().ok()?,
+            score: numbers.next()?.parse().ok()?,
+        };
+        numbers.next().is_none().then_some(rule)
+    }
+}
+
+impl GameRecord {
+    /// contender indices of white and black
+    pub fn colours(&self) -> [usize; 2] {
+        [self.white, 1 - self.white]
+    }
+
+    /// points of the first contender, `None` for unfinished games
+    pub fn points(&self) -> Option<f64> {
+        let white = match self.game.result {
+            GameResult::WhiteWins => 1.0,
+            GameResult::BlackWins => 0.0,
+            GameResult::Draw => 0.5,
+            GameResult::Unknown => return None,
+        };
+        Some(if self.white == 0 { white } else { 1.0 - white })
+    }
+}
+
+impl Score {
+    pub fn add(&mut self, record: &GameRecord) {
+        match record.points() {
+            Some(points) if points > 0.75 => self.wins += 1,
+            Some(points) if points < 0.25 => self.losses += 1,
+            Some(_) => self.draws += 1,
+            None => {}
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// points per game, 0.5 without games
+    pub fn ratio(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 * 0.5) / self.games() as f64
+    }
+
+    /// elo difference of the first contender
+    pub fn elo(&self) -> f64 {
+        elo(self.ratio())
+    }
+
+    /// half width of the 95% confidence interval of [`Score::elo`]
+    pub fn elo_error(&self) -> f64 {
+        let n = self.games() as f64;
+        if n == 0.0 {
+            return f64::INFINITY;
+        }
+        let p = self.ratio();
+        let variance = (self.wins as f64 * (1.0 - p).powi(2)
+            + self.draws as f64 * (0.5 - p).powi(2)
+            + self.losses as f64 * p.powi(2))
+            / n;
+        let error = 1.96 * (variance / n).sqrt();
+        (elo(p + error) - elo(p - error)) / 2.0
+    }
+
+    /// results of both contenders with the elo difference
+    pub fn table(&self, contenders: &[Contender; 2]) -> String {
+        let mirrored = Score {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        };
+        let width = contenders
+            .iter()
+            .map(|contender| contender.name.len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+
+        let mut out = format!(
+            "{:width$}  {:>5}  {:>5}  {:>5}  {:>6}  {:>6}\n",
+            "engine", "games", "wins", "draws", "losses", "score"
+        );
+        for (contender, score) in contenders.iter().zip([self, &mirrored]) {
+            out += &format!(
+                "{:width$}  {:>5}  {:>5}  {:>5}  {:>6}  {:>5.1}%\n",
+                contender.name,
+                score.games(),
+                score.wins,
+                score.draws,
+                score.losses,
+                score.ratio() * 100.0,
+            );
+        }
+        out += &format!(
+            "elo difference: {:+.1} +/- {:.1}\n",
+            self.elo(),
+            self.elo_error()
+        );
+        out
+    }
+}
+
+impl Pairing {
+    /// the points of both games once `record` completes its pair, pairs with
+    /// an unfinished game are dropped
+    fn add(&mut self, record: &GameRecord) -> Option<(f64, f64)> {
+        let pair = record.index / 2;
+        let Some(first) = self.halves.remove(&pair) else {
+            self.halves.insert(pair, record.points());
+            return None;
+        };
+        match (first, record.points()) {
+            (Some(first), Some(second)) => Some((first, second)),
+            _ => {
+                log::warn!(
+                    "games {} and {} left out of the sprt, one has no result",
+                    2 * pair + 1,
+                    2 * pair + 2
+                );
+                None
+            }
+        }
+    }
+}
+
+impl Player {
+    fn new(config: &EngineConfig) -> io::Result<Self> {
+        Ok(match config {
+            EngineConfig::Builtin(options) => Player::Builtin(Search::new(*options)),
+            EngineConfig::External(path) => Player::External(UciEngine::spawn(path)?),
+        })
+    }
+
+    fn new_game(&mut self) -> io::Result<()> {
+        match self {
+            Player::Builtin(search) => {
+                search.clear();
+                Ok(())
+            }
+            Player::External(engine) => engine.new_game(),
+        }
+    }
+
+    /// the move and its score for the side to move after `moves` from
+    /// `opening`, `None` if the engine had no move or overstepped `deadline`
+    fn go(
+        &mut self,
+        opening: &Opening,
+        moves: &[Move],
+        limits: Limits,
+        opponent_time: Option<Duration>,
+        deadline: Option<Instant>,
+    ) -> io::Result<(Option<Move>, Option<i32>)> {
+        match self {
+            Player::Builtin(search) => {
+                let mut board = opening.board.clone();
+                for &mv in moves {
+                    board.play(mv);
+                }
+                let side = if moves.len().is_multiple_of(2) {
+                    opening.side
+                } else {
+                    opening.side.other()
+                };
+                let result = search.go(&board, side, limits, Stop::new(), |_| {});
+                Ok((result.best, Some(result.score)))
+            }
+            Player::External(engine) => {
+                // the whole game goes to the engine so it knows the castling
+                // rights and repetitions
+                engine.go(&opening.board, opening.side, moves, limits, opponent_time)?;
+                loop {
+                    if let Some(best) = engine.poll()? {
+                        let score = engine.infos().first().map(|info| info.score);
+                        return Ok((best.mv, score));
+                    }
+                    if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                        engine.stop()?;
+                        return Ok((None, None));
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+}
+
+//
+
+fn play_game(config: &MatchConfig, index: usize, players: &mut [Player; 2]) -> GameRecord {
+    let start_position = Opening {
+        board: Board::starting(),
+        side: Side::White,
+        moves: Vec::new(),
+    };
+    let opening = match config.openings.len() {
+        0 => &start_position,
+        n => &config.openings[index / 2 % n],
+    };
+    let white = index % 2;
+    let names = [white, 1 - white].map(|i| config.contenders[i].name.clone());
+
+    let mut game = Game::new();
+    game.board = opening.board.clone();
+    game.side = opening.side;
+    game.tags = vec![
+        ("Event".to_string(), "match".to_string()),
+        ("Round".to_string(), (index + 1).to_string()),
+        ("White".to_string(), names[0].clone()),
+        ("Black".to_string(), names[1].clone()),
+    ];
+    let fen = opening.board.to_fen(opening.side);
+    if fen != Board::starting().to_fen(Side::White) {
+        game.tags.push(("SetUp".to_string(), "1".to_string()));
+        game.tags.push(("FEN".to_string(), fen));
+    }
+
+    let (result, reason) = match players.iter_mut().try_for_each(Player::new_game) {
+        Ok(()) => play_moves(config, opening, white, players, &mut game.moves, &names),
+        Err(err) => (
+            GameResult::Unknown,
+            format!("engine failed to start: {err}"),
+        ),
+    };
+    game.result = result;
+    game.tags.push(("Result".to_string(), result.to_string()));
+    game.tags.push(("Termination".to_string(), reason.clone()));
+
+    GameRecord {
+        index,
+        white,
+        game,
+        reason,
+    }
+}
+
+/// plays until the game ends, returns the result and why
+fn play_moves(
+    config: &MatchConfig,
+    opening: &Opening,
+    white: usize,
+    players: &mut [Player; 2],
+    moves: &mut Vec<Move>,
+    names: &[String; 2],
+) -> (GameResult, String) {
+    let loss = |side: Side| match side {
+        Side::White => GameResult::BlackWins,
+        Side::Black => GameResult::WhiteWins,
+    };
+
+    let mut board = opening.board.clone();
+    let mut side = opening.side;
+    let mut repetitions = HashMap::<u64, u32>::new();
+    // plies since the last capture or pawn move
+    let mut quiet = 0;
+    for &mv in opening.moves.iter() {
+        board.play(mv);
+        side = side.other();
+        moves.push(mv);
+    }
+
+    let (mut clocks, increment, period) = match config.time_control {
+        TimeControl::Clock {
+            time,
+            increment,
+            moves,
+        } => ([Some(time); 2], increment, moves),
+        TimeControl::Fixed(_) => ([None; 2], Duration::ZERO, None),
+    };
+    let mut played = [0u32; 2];
+    let mut draw_plies = 0;
+    let mut resign_moves = [0u32; 2];
+
+    loop {
+        let legal = board.legal_moves(side);
+        if legal.is_empty() {
+            return if board.in_check(side) {
+                (loss(side), format!("{} mates", side_name(side.other())))
+            } else {
+                (GameResult::Draw, "stalemate".to_string())
+            };
+        }
+        let repeated = repetitions.entry(board.hash(side)).or_default();
+        *repeated += 1;
+        if *repeated >= 3 {
+            return (GameResult::Draw, "threefold repetition".to_string());
+        }
+        if quiet >= 100 {
+            return (GameResult::Draw, "fifty move rule".to_string());
+        }
+        if insufficient_material(&board) {
+            return (GameResult::Draw, "insufficient material".to_string());
+        }
+        if config
+            .adjudication
+            .max_moves
+            .is_some_and(|max| played.iter().all(|&n| n >= max))
+        {
+            return (GameResult::Draw, "move limit".to_string());
+        }
+
+        let s = side as usize;
+        let engine = if side == Side::White {
+            white
+        } else {
+            1 - white
+        };
+        let limits = match (config.time_control, clocks[s]) {
+            (TimeControl::Fixed(limits), _) => limits,
+            (_, clock) => Limits {
+                time: clock,
+                increment,
+                moves_to_go: period.map(|period| period - played[s] % period),
+                ..Default::default()
+            },
+        };
+        let start = Instant::now();
+        let deadline = clocks[s].map(|clock| start + clock + TIME_GRACE);
+        let result = players[engine].go(opening, moves, limits, clocks[1 - s], deadline);
+        let elapsed = start.elapsed();
+
+        let (mv, score) = match result {
+            Ok(ok) => ok,
+            Err(err) => return (loss(side), format!("{} failed: {err}", names[s])),
+        };
+        if let Some(clock) = clocks[s].as_mut() {
+            if elapsed > *clock {
+                return (loss(side), format!("{} lost on time", names[s]));
+            }
+            *clock = *clock - elapsed + increment;
+        }
+        let mv = match mv.filter(|mv| legal.contains(mv)) {
+            Some(mv) => mv,
+            None => return (loss(side), format!("{} played an illegal move", names[s])),
+        };
+
+        played[s] += 1;
+        if let (Some(period), Some(clock)) = (period, clocks[s].as_mut()) {
+            if played[s] % period == 0 {
+                if let TimeControl::Clock { time, .. } = config.time_control {
+                    *clock += time;
+                }
+            }
+        }
+
+        if let Some(score) = score {
+            if let Some(rule) = config.adjudication.resign {
+                if score <= -rule.score {
+                    resign_moves[s] += 1;
+                } else {
+                    resign_moves[s] = 0;
+                }
+                if resign_moves[s] >= rule.moves {
+                    return (loss(side), format!("{} resigns", names[s]));
+                }
+            }
+            if let Some(rule) = config.adjudication.draw {
+                let move_number = moves.len() as u32 / 2 + 1;
+                if move_number >= rule.move_number && score.abs() <= rule.score {
+                    draw_plies += 1;
+                } else {
+                    draw_plies = 0;
+                }
+                if draw_plies >= 2 * rule.moves {
+                    return (GameResult::Draw, "draw adjudication".to_string());
+                }
+            }
+        }
+
+        let zeroing = board.get_piece(&mv.to).is_some()
+            || board
+                .get_piece(&mv.from)
+                .is_some_and(|(_, piece)| piece == Piece::Pawn);
+        quiet = if zeroing { 0 } else { quiet + 1 };
+        if zeroing {
+            repetitions.clear();
+        }
+
+        board.play(mv);
+        moves.push(mv);
+        side = side.other();
+    }
+}
+
+/// only kings and at most one knight or bishop left
+fn insufficient_material(board: &Board) -> bool {
+    let mut minors = 0;
+    for (_, piece, _) in board.iter() {
+        match piece {
+            Piece::King => {}
+            Piece::Knight | Piece::Bishop => minors += 1,
+            _ => return false,
+        }
+    }
+    minors <= 1
+}
+
+fn elo(ratio: f64) -> f64 {
+    let ratio = ratio.clamp(1e-6, 1.0 - 1e-6);
+    400.0 * (ratio / (1.0 - ratio)).log10()
+}
+
+/// a number of seconds, fractions allowed
+fn seconds(s: &str) -> Option<Duration> {
+    let secs = s.parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::White => "white",
+        Side::Black => "black",
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_arguments() {
+        let contender = Contender::parse("builtin:Threads=2,LMR=false,Elo=1500").unwrap();
+        let EngineConfig::Builtin(options) = contender.engine else {
+            panic!("not builtin");
+        };
+        assert_eq!(options.threads, 2);
+        assert!(!options.techniques.lmr);
+        assert_eq!(options.strength, Strength::from_elo(1500));
+        assert_eq!(Contender::parse("builtin"), Some(Contender::builtin()));
+        assert_eq!(Contender::parse("builtin:Nope=1"), None);
+        assert_eq!(
+            Contender::parse("/usr/bin/stockfish").unwrap().engine,
+            EngineConfig::External("/usr/bin/stockfish".to_string())
+        );
+
+        assert_eq!(
+            TimeControl::parse("40/60+0.5"),
+            Some(TimeControl::Clock {
+                time: Duration::from_secs(60),
+                increment: Duration::from_millis(500),
+                moves: Some(40),
+            })
+        );
+        assert_eq!(TimeControl::parse("-1"), None);
+        assert_eq!(TimeControl::parse("1e20"), None);
+        assert_eq!(TimeControl::parse("60+NaN"), None);
+        assert_eq!(DrawRule::parse("40,8,10").map(|rule| rule.moves), Some(8));
+        assert_eq!(ResignRule::parse("3,600,1"), None);
+    }
+
+    #[test]
+    fn elo_of_scores() {
+        let even = Score {
+            wins: 10,
+            draws: 10,
+            losses: 10,
+        };
+        assert_eq!(even.elo(), 0.0);
+        assert!(even.elo_error() > 0.0 && even.elo_error().is_finite());
+
+        let ahead = Score {
+            wins: 30,
+            draws: 40,
+            losses: 10,
+        };
+        // 62.5% is a bit under 90 elo
+        assert!((ahead.elo() - 88.7).abs() < 0.1, "{}", ahead.elo());
+        let behind = Score {
+            wins: 10,
+            draws: 40,
+            losses: 30,
+        };
+        assert!((ahead.elo() + behind.elo()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pairs_games_with_results() {
+        let record = |index, result| {
+            let mut game = Game::new();
+            game.result = result;
+            GameRecord {
+                index,
+                white: index % 2,
+                game,
+                reason: String::new(),
+            }
+        };
+
+        let mut pairing = Pairing::default();
+        assert_eq!(pairing.add(&record(1, GameResult::WhiteWins)), None);
+        assert_eq!(pairing.add(&record(2, GameResult::Unknown)), None);
+        assert_eq!(pairing.add(&record(0, GameResult::Draw)), Some((0.0, 0.5)));
+        // the unfinished game takes its partner with it
+        assert_eq!(pairing.add(&record(3, GameResult::WhiteWins)), None);
+        assert!(pairing.halves.is_empty());
+        assert_eq!(pairing.add(&record(5, GameResult::Unknown)), None);
+        assert_eq!(pairing.add(&record(4, GameResult::BlackWins)), None);
+        assert!(pairing.halves.is_empty());
+    }
+
+    #[test]
+    fn plays_a_short_match() {
+        let config = MatchConfig {
+            contenders: [Contender::builtin(), Contender::builtin()],
+            games: 4,
+            concurrency: 2,
+            time_control: TimeControl::Fixed(Limits::depth(1)),
+            openings: Vec::new(),
+            adjudication: Adjudication {
+                max_moves: Some(10),
+                ..Default::default()
+            },
+        };
+
+        let mut records = Vec::new();
+        let score = play_match(&config, |record, _| {
+            records.push(record.clone());
+            true
+        })
+        .unwrap();
+        assert_eq!(score.games(), 4);
+
+        records.sort_by_key(|record| record.index);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.index, i);
+            // colours alternate within every pair
+            assert_eq!(record.white, i % 2);
+            assert_ne!(record.game.result, GameResult::Unknown);
+            assert!(record.game.moves.len() <= 20);
+            assert_eq!(record.game.tag("Termination"), Some(record.reason.as_str()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn promotes_in_a_match() {
+        let path = crate::uci_client::tests::stub("promotion");
+        let (board, side) = Board::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let config = MatchConfig {
+            contenders: [
+                Contender::parse(path.to_str().unwrap()).unwrap(),
+                Contender::builtin(),
+            ],
+            games: 2,
+            concurrency: 1,
+            time_control: TimeControl::Fixed(Limits::depth(3)),
+            openings: vec![Opening {
+                board,
+                side,
+                moves: Vec::new(),
+            }],
+            adjudication: Adjudication {
+                max_moves: Some(4),
+                ..Default::default()
+            },
+        };
+
+        let mut records = Vec::new();
+        play_match(&config, |record, _| {
+            records.push(record.clone());
+            true
+        })
+        .unwrap();
+        fs::remove_file(path).unwrap();
+
+        records.sort_by_key(|record| record.index);
+        // the stub and the builtin search both queen as white, the stub
+        // loses to its next move either way
+        for record in records.iter() {
+            let first = record.game.moves[0];
+            assert_eq!(first, Move::parse("a7a8q").unwrap(), "{}", record.reason);
+            assert_eq!(first.promotion, Some(Piece::Queen));
+            assert!(record.reason.ends_with("played an illegal move"));
+        }
+    }
+}
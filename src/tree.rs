@@ -0,0 +1,548 @@
+use crate::{
+    board::Move,
+    pgn::{parse_san, read_tag, to_san, write_movetext, write_tags, Game, GameResult},
+    piece::Side,
+    Board,
+};
+use core::fmt;
+
+//
+
+/// a game with its variations
+///
+/// every position lists the moves tried from it, the first one continues
+/// the main line, the rest are variations
+///
+/// a node is found by its path, the child index taken at every move from
+/// the start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameTree {
+    pub tags: Vec<(String, String)>,
+    pub board: Board,
+    pub side: Side,
+    /// before the first move
+    pub comment: Option<String>,
+    pub children: Vec<Node>,
+    pub result: GameResult,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub mv: Move,
+    /// numeric annotation glyphs, `$1` is a good move
+    pub nags: Vec<u8>,
+    /// after the move
+    pub comment: Option<String>,
+    pub children: Vec<Node>,
+}
+
+enum Token {
+    Tag(String, String),
+    San(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+    Result(GameResult),
+}
+
+//
+
+impl GameTree {
+    pub fn new(board: Board, side: Side) -> Self {
+        Self {
+            tags: Vec::new(),
+            board,
+            side,
+            comment: None,
+            children: Vec::new(),
+            result: GameResult::Unknown,
+        }
+    }
+
+    /// the first game of a pgn text with its variations, comments and nags
+    ///
+    /// a line stops at the first move that cannot be played, `None` without
+    /// any tag or move
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut tree = GameTree::new(Board::starting(), Side::White);
+        let mut any = false;
+        let mut path = Vec::new();
+        // paths to return to at the end of the open variations
+        let mut stack = Vec::new();
+        // variation depth of the line with an unplayable move
+        let mut broken: Option<usize> = None;
+
+        for token in tokens(text) {
+            match token {
+                Token::Tag(name, value) => {
+                    if !tree.children.is_empty() {
+                        break;
+                    }
+                    if name == "FEN" {
+                        match Board::from_fen(&value) {
+                            Ok((board, side)) => {
+                                tree.board = board;
+                                tree.side = side;
+                            }
+                            Err(err) => log::warn!("invalid fen '{value}': {err}"),
+                        }
+                    }
+                    tree.tags.push((name, value));
+                }
+                Token::San(_) | Token::Nag(_) | Token::Comment(_)
+                    if broken.is_some_and(|depth| stack.len() >= depth) => {}
+                Token::San(san) => {
+                    let (board, side) = tree.position(&path);
+                    match parse_san(&board, side, &san) {
+                        Some(mv) => {
+                            tree.play(&mut path, mv);
+                        }
+                        None => {
+                            log::warn!("unplayable move '{san}' after {} plies", path.len());
+                            broken = Some(stack.len());
+                        }
+                    }
+                }
+                Token::Nag(nag) => {
+                    if let Some(node) = tree.node_mut(&path) {
+                        node.nags.push(nag);
+                    }
+                }
+                Token::Comment(comment) => {
+                    let slot = match tree.node_mut(&path) {
+                        Some(node) => &mut node.comment,
+                        None => &mut tree.comment,
+                    };
+                    match slot {
+                        Some(text) => {
+                            text.push(' ');
+                            text.push_str(&comment);
+                        }
+                        None => *slot = Some(comment),
+                    }
+                }
+                // a variation replaces the last move
+                Token::Open => {
+                    let mut parent = path.clone();
+                    parent.pop();
+                    stack.push(std::mem::replace(&mut path, parent));
+                }
+                Token::Close => {
+                    if let Some(outer) = stack.pop() {
+                        path = outer;
+                    }
+                    if broken.is_some_and(|depth| stack.len() < depth) {
+                        broken = None;
+                    }
+                }
+                Token::Result(result) => {
+                    tree.result = result;
+                    any = true;
+                    break;
+                }
+            }
+            any = true;
+        }
+
+        any.then_some(tree)
+    }
+
+    /// the position after the moves to `path`
+    pub fn position(&self, path: &[usize]) -> (Board, Side) {
+        let mut board = self.board.clone();
+        let mut side = self.side;
+        let mut children = self.children.as_slice();
+        for &i in path {
+            let node = match children.get(i) {
+                Some(node) => node,
+                None => break,
+            };
+            board.play(node.mv);
+            side = side.other();
+            children = &node.children;
+        }
+        (board, side)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// replaces or adds a tag
+    pub fn set_tag(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.tags.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn node(&self, path: &[usize]) -> Option<&Node> {
+        let (&last, parent) = path.split_last()?;
+        self.children(parent)?.get(last)
+    }
+
+    pub fn node_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        let (&last, parent) = path.split_last()?;
+        self.children_mut(parent)?.get_mut(last)
+    }
+
+    /// the moves tried after `path`
+    pub fn children(&self, path: &[usize]) -> Option<&[Node]> {
+        path.iter()
+            .try_fold(&self.children, |children, &i| {
+                children.get(i).map(|node| &node.children)
+            })
+            .map(Vec::as_slice)
+    }
+
+    fn children_mut(&mut self, path: &[usize]) -> Option<&mut Vec<Node>> {
+        path.iter().try_fold(&mut self.children, |children, &i| {
+            children.get_mut(i).map(|node| &mut node.children)
+        })
+    }
+
+    /// the main line continuation after `path`
+    pub fn main_line(&self, path: &[usize]) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut children = self.children(path).unwrap_or_default();
+        while let Some(node) = children.first() {
+            moves.push(node.mv);
+            children = &node.children;
+        }
+        moves
+    }
+
+    /// follows `mv` from `path`, a move not tried before becomes the main
+    /// line of a new position or a new variation
+    ///
+    /// `true` if the move was new
+    pub fn play(&mut self, path: &mut Vec<usize>, mv: Move) -> bool {
+        let children = self.children_mut(path).expect("valid path");
+        match children.iter().position(|node| node.mv == mv) {
+            Some(i) => {
+                path.push(i);
+                false
+            }
+            None => {
+                children.push(Node::new(mv));
+                path.push(children.len() - 1);
+                true
+            }
+        }
+    }
+
+    /// moves the variation `path` is in one place closer to the main line,
+    /// `false` on the main line
+    pub fn promote(&mut self, path: &mut [usize]) -> bool {
+        let level = match path.iter().rposition(|&i| i != 0) {
+            Some(level) => level,
+            None => return false,
+        };
+        let i = path[level];
+        let children = self.children_mut(&path[..level]).expect("valid path");
+        children.swap(i - 1, i);
+        path[level] = i - 1;
+        true
+    }
+
+    /// removes the variation `path` is in, on the main line the node and
+    /// everything after it
+    ///
+    /// returns the length of the path to the position the variation
+    /// started from, `path` past it is no longer valid
+    pub fn delete(&mut self, path: &[usize]) -> Option<usize> {
+        let level = match path.iter().rposition(|&i| i != 0) {
+            Some(level) => level,
+            None => path.len().checked_sub(1)?,
+        };
+        let children = self.children_mut(&path[..level])?;
+        children.remove(path[level]);
+        Some(level)
+    }
+
+    /// sets or clears a nag of the node at `path`
+    pub fn toggle_nag(&mut self, path: &[usize], nag: u8) {
+        if let Some(node) = self.node_mut(path) {
+            match node.nags.iter().position(|&n| n == nag) {
+                Some(i) => {
+                    node.nags.remove(i);
+                }
+                None => node.nags.push(nag),
+            }
+        }
+    }
+
+    /// the movetext tokens of the line starting with `children` from
+    /// `board`, variations in parentheses
+    fn tokens(
+        tokens: &mut Vec<String>,
+        board: &Board,
+        mut side: Side,
+        mut number: u32,
+        mut children: &[Node],
+    ) {
+        let mut board = board.clone();
+        // black moves are numbered at the start and after an interruption
+        let mut numbered = true;
+        while let Some(main) = children.first() {
+            main.tokens(tokens, &board, side, number, numbered);
+
+            for variation in children[1..].iter() {
+                let start = tokens.len();
+                Self::tokens(
+                    tokens,
+                    &board,
+                    side,
+                    number,
+                    std::slice::from_ref(variation),
+                );
+                tokens[start].insert(0, '(');
+                tokens.last_mut().expect("a move").push(')');
+            }
+
+            numbered = children.len() > 1 || main.comment.is_some();
+            board.play(main.mv);
+            if side == Side::Black {
+                number += 1;
+            }
+            side = side.other();
+            children = &main.children;
+        }
+    }
+}
+
+impl Node {
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            nags: Vec::new(),
+            comment: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn tokens(
+        &self,
+        tokens: &mut Vec<String>,
+        board: &Board,
+        side: Side,
+        number: u32,
+        numbered: bool,
+    ) {
+        match side {
+            Side::White => tokens.push(format!("{number}.")),
+            Side::Black if numbered => tokens.push(format!("{number}...")),
+            Side::Black => {}
+        }
+        tokens.push(to_san(board, side, self.mv));
+        tokens.extend(self.nags.iter().map(|nag| format!("${nag}")));
+        if let Some(comment) = self.comment.as_ref() {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+        }
+    }
+}
+
+impl From<&Game> for GameTree {
+    /// the main line of `game`
+    fn from(game: &Game) -> Self {
+        let mut tree = GameTree::new(game.board.clone(), game.side);
+        tree.tags = game.tags.clone();
+        tree.result = game.result;
+        let mut path = Vec::new();
+        for &mv in game.moves.iter() {
+            tree.play(&mut path, mv);
+        }
+        tree
+    }
+}
+
+impl fmt::Display for GameTree {
+    /// pgn with every variation, comment and nag
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tags(f, &self.tags)?;
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        if let Some(comment) = self.comment.as_ref() {
+            tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+        }
+        Self::tokens(
+            &mut tokens,
+            &self.board,
+            self.side,
+            self.board.fullmove(),
+            &self.children,
+        );
+        tokens.push(self.result.to_string());
+        write_movetext(f, tokens)
+    }
+}
+
+//
+
+fn tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let comment = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                tokens.push(Token::Comment(comment));
+            }
+            ';' => {
+                let comment = chars
+                    .by_ref()
+                    .take_while(|&c| c != '\n')
+                    .collect::<String>();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '[' => {
+                if let Some((name, value)) = read_tag(&mut chars) {
+                    tokens.push(Token::Tag(name, value));
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+
+                if let Some(nag) = word.strip_prefix('$') {
+                    tokens.extend(nag.parse().ok().map(Token::Nag));
+                    continue;
+                }
+                if let Some(result) = GameResult::parse(&word) {
+                    tokens.push(Token::Result(result));
+                    continue;
+                }
+
+                // `12.`, `12...` and `12.e4`, suffixes like `!?` as nags
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let glyph = san.trim_start_matches(|c: char| c != '!' && c != '?');
+                let san = &san[..san.len() - glyph.len()];
+                if !san.is_empty() {
+                    tokens.push(Token::San(san.to_string()));
+                }
+                tokens.extend(
+                    (1..=6)
+                        .find(|&nag| nag_glyph(nag) == Some(glyph))
+                        .map(Token::Nag),
+                );
+            }
+        }
+    }
+
+    tokens
+}
+
+/// the move suffix of the first six nags
+pub fn nag_glyph(nag: u8) -> Option<&'static str> {
+    match nag {
+        1 => Some("!"),
+        2 => Some("?"),
+        3 => Some("!!"),
+        4 => Some("??"),
+        5 => Some("!?"),
+        6 => Some("?!"),
+        _ => None,
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = "[Event \"t\"]\n\n\
+        1. e4 e5 (1... c5 2. Nf3 (2. c3) 2... d6) 2. Nf3 $1 {develops} 2... Nc6 1-0\n";
+
+    fn sans(tree: &GameTree, path: &[usize]) -> Vec<String> {
+        let (mut board, mut side) = tree.position(path);
+        let mut sans = Vec::new();
+        for mv in tree.main_line(path) {
+            sans.push(to_san(&board, side, mv));
+            board.play(mv);
+            side = side.other();
+        }
+        sans
+    }
+
+    #[test]
+    fn roundtrip() {
+        let tree = GameTree::parse(PGN).unwrap();
+        assert_eq!(tree.tag("Event"), Some("t"));
+        assert_eq!(tree.result, GameResult::WhiteWins);
+        assert_eq!(sans(&tree, &[]), ["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(sans(&tree, &[0, 1]), ["Nf3", "d6"]);
+        assert_eq!(tree.node(&[0, 0, 0]).unwrap().nags, [1]);
+        assert_eq!(
+            tree.node(&[0, 0, 0]).unwrap().comment.as_deref(),
+            Some("develops")
+        );
+
+        assert_eq!(tree.to_string(), PGN);
+        assert_eq!(GameTree::parse(&tree.to_string()), Some(tree));
+    }
+
+    #[test]
+    fn unplayable_moves_end_the_line() {
+        let tree = GameTree::parse("1. e4 e5 2. Ke3 (2. Nf3) Nc6 *").unwrap();
+        assert_eq!(sans(&tree, &[]), ["e4", "e5"]);
+
+        // only the variation is cut short
+        let tree = GameTree::parse("1. e4 e5 (1... Ke7 c5) 2. Nf3 *").unwrap();
+        assert_eq!(sans(&tree, &[]), ["e4", "e5", "Nf3"]);
+        assert_eq!(tree.children(&[0]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn numbers_from_the_fen() {
+        let pgn = "[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n\n\
+            12... Kd7 13. e4 *\n";
+        let tree = GameTree::parse(pgn).unwrap();
+        assert_eq!(tree.board.fullmove(), 12);
+        assert_eq!(tree.to_string(), pgn);
+    }
+
+    #[test]
+    fn edits() {
+        let mut tree = GameTree::parse(PGN).unwrap();
+
+        // a move tried before is followed, a new one branches off
+        let (board, side) = tree.position(&[0]);
+        let mut path = vec![0];
+        assert!(!tree.play(&mut path, parse_san(&board, side, "c5").unwrap()));
+        assert_eq!(path, [0, 1]);
+        let mut other = vec![0];
+        assert!(tree.play(&mut other, parse_san(&board, side, "e6").unwrap()));
+        assert_eq!(other, [0, 2]);
+
+        assert!(tree.promote(&mut path));
+        assert_eq!(path, [0, 0]);
+        assert_eq!(sans(&tree, &[]), ["e4", "c5", "Nf3", "d6"]);
+        assert!(!tree.promote(&mut path));
+
+        // a variation goes back to where it started, the main line to the
+        // move before
+        assert_eq!(tree.delete(&[0, 0, 1]), Some(2));
+        assert_eq!(tree.children(&[0, 0]).unwrap().len(), 1);
+        assert_eq!(tree.delete(&[0, 0, 0]), Some(2));
+        assert_eq!(sans(&tree, &[]), ["e4", "c5"]);
+        assert_eq!(tree.delete(&[]), None);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{board::Move, eval::MATE_BOUND, piece::Piece, BoardPos};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Bound {
+    Exact = 1,
+    Lower = 2,
+    Upper = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+/// transposition table shared between search threads without locks
+///
+/// every slot stores `key ^ data` next to `data`, a torn write from two
+/// threads racing on the same slot fails the key check and reads as a miss
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+//
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> Self {
+        let len = (mb.max(1) * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        Self {
+            slots: (0..len).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn probe(&self, hash: u64, ply: u8) -> Option<TtEntry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        if data == 0 || key ^ data != hash {
+            return None;
+        }
+
+        let mut entry = unpack(data)?;
+        entry.score = score_from_tt(entry.score, ply);
+        Some(entry)
+    }
+
+    pub fn store(&self, hash: u64, ply: u8, entry: TtEntry) {
+        let slot = self.slot(hash);
+
+        // keep deeper results of the same position
+        let old = slot.data.load(Ordering::Relaxed);
+        let old_key = slot.key.load(Ordering::Relaxed);
+        if old_key ^ old == hash {
+            if let Some(old) = unpack(old) {
+                if old.depth > entry.depth && entry.bound != Bound::Exact {
+                    return;
+                }
+            }
+        }
+
+        let data = pack(TtEntry {
+            score: score_to_tt(entry.score, ply),
+            ..entry
+        });
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    /// permille of used slots in the first thousand
+    pub fn hashfull(&self) -> usize {
+        self.slots
+            .iter()
+            .take(1000)
+            .filter(|slot| slot.data.load(Ordering::Relaxed) != 0)
+            .count()
+            * 1000
+            / self.slots.len().min(1000)
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[(hash % self.slots.len() as u64) as usize]
+    }
+}
+
+// data layout:
+//  0..16  score
+// 16..24  depth
+// 24..26  bound
+// 26..32  from
+// 32..38  to
+// 38      has move
+// 39..42  promotion piece + 1
+
+fn pack(entry: TtEntry) -> u64 {
+    let mut data = (entry.score as i16 as u16 as u64)
+        | (entry.depth as u64) << 16
+        | (entry.bound as u64) << 24;
+    if let Some(mv) = entry.best {
+        data |= (mv.from.to_usize() as u64) << 26 | (mv.to.to_usize() as u64) << 32 | 1 << 38;
+        data |= mv.promotion.map_or(0, |piece| piece as u64 + 1) << 39;
+    }
+    data
+}
+
+fn unpack(data: u64) -> Option<TtEntry> {
+    let bound = match (data >> 24) & 0b11 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let best = if data >> 38 & 1 == 1 {
+        Some(Move {
+            from: BoardPos::from_usize((data >> 26 & 0x3f) as usize)?,
+            to: BoardPos::from_usize((data >> 32 & 0x3f) as usize)?,
+            promotion: match data >> 39 & 0b111 {
+                0 => None,
+                2 => Some(Piece::Knight),
+                3 => Some(Piece::Bishop),
+                4 => Some(Piece::Rook),
+                5 => Some(Piece::Queen),
+                _ => return None,
+            },
+        })
+    } else {
+        None
+    };
+
+    Some(TtEntry {
+        best,
+        score: data as u16 as i16 as i32,
+        depth: (data >> 16) as u8,
+        bound,
+    })
+}
+
+// mate scores are stored relative to the node, not the root
+
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::MATE;
+
+    fn entry(score: i32, best: Option<Move>, bound: Bound) -> TtEntry {
+        TtEntry {
+            best,
+            score,
+            depth: 7,
+            bound,
+        }
+    }
+
+    #[test]
+    fn pack_unpack() {
+        let mv = Move {
+            from: BoardPos::parse("a1").unwrap(),
+            to: BoardPos::parse("h8").unwrap(),
+            promotion: None,
+        };
+        let promotion = Move::parse("b2b1n").unwrap();
+        for entry in [
+            entry(0, None, Bound::Exact),
+            entry(-1234, Some(mv), Bound::Lower),
+            entry(55, Some(promotion), Bound::Lower),
+            entry(MATE - 3, Some(mv), Bound::Upper),
+            entry(-MATE + 3, None, Bound::Exact),
+        ] {
+            assert_eq!(unpack(pack(entry)), Some(entry));
+        }
+        // an empty slot
+        assert_eq!(unpack(0), None);
+    }
+
+    #[test]
+    fn mate_scores_move_with_ply() {
+        // mate in 5 plies from the root, stored 3 plies deep
+        assert_eq!(score_to_tt(MATE - 5, 3), MATE - 2);
+        assert_eq!(score_from_tt(MATE - 2, 3), MATE - 5);
+        // the same position reached 7 plies deep
+        assert_eq!(score_from_tt(MATE - 2, 7), MATE - 9);
+        assert_eq!(score_to_tt(-MATE + 5, 3), -MATE + 2);
+        assert_eq!(score_from_tt(-MATE + 2, 7), -MATE + 9);
+
+        assert_eq!(score_to_tt(150, 9), 150);
+        assert_eq!(score_from_tt(-150, 9), -150);
+    }
+
+    #[test]
+    fn store_probe() {
+        let tt = TranspositionTable::new(1);
+        tt.store(0xdead_beef, 4, entry(MATE - 6, None, Bound::Exact));
+        assert_eq!(
+            tt.probe(0xdead_beef, 2),
+            Some(entry(MATE - 4, None, Bound::Exact))
+        );
+        assert_eq!(tt.probe(0xdead_bee0, 2), None);
+
+        // a shallower bound does not replace a deeper result
+        tt.store(
+            0xdead_beef,
+            0,
+            TtEntry {
+                depth: 1,
+                ..entry(10, None, Bound::Lower)
+            },
+        );
+        assert_eq!(tt.probe(0xdead_beef, 4).map(|entry| entry.depth), Some(7));
+
+        tt.clear();
+        assert_eq!(tt.probe(0xdead_beef, 4), None);
+    }
+}
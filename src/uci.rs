@@ -0,0 +1,553 @@
+use crate::{
+    board::Move,
+    book::{Book, Selection},
+    eval::{is_mate, MATE},
+    piece::Side,
+    search::{Search, SearchInfo, SearchOptions, Techniques},
+    strength::{Strength, MAX_ELO, MIN_ELO},
+    tablebase::Tablebase,
+    time::{Limits, Pondering, Stop},
+    Board,
+};
+use std::{
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+//
+
+/// uci protocol engine side
+///
+/// commands are read from `input` and answers written to `out`, searches run
+/// on a background thread so `stop` and `isready` are answered while thinking
+pub struct Uci<W: Write + Send + 'static> {
+    out: Arc<Mutex<W>>,
+
+    board: Board,
+    side: Side,
+
+    /// `None` while a search thread owns it
+    search: Option<Search>,
+    searching: Option<(JoinHandle<Search>, Stop, Pondering)>,
+
+    own_book: bool,
+    book_file: String,
+    /// loaded once `OwnBook` is turned on
+    book: Option<Book>,
+
+    limit_strength: bool,
+    elo: u32,
+}
+
+/// arguments of a `go` command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Go {
+    pub limits: Limits,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+//
+
+pub const NAME: &str = concat!("chess ", env!("CARGO_PKG_VERSION"));
+
+pub const AUTHOR: &str = "Overpeek";
+
+pub const DEFAULT_BOOK_FILE: &str = "book.bin";
+
+//
+
+/// runs the engine on stdin and stdout until `quit`
+pub fn run() -> io::Result<()> {
+    Uci::new(io::stdout()).run(io::stdin().lock())
+}
+
+/// `cp 13` or `mate -3` as used by `info score`
+pub fn format_score(score: i32) -> String {
+    if is_mate(score) {
+        let moves = if score > 0 {
+            (MATE - score + 1) / 2
+        } else {
+            -(MATE + score) / 2
+        };
+        format!("mate {moves}")
+    } else {
+        format!("cp {score}")
+    }
+}
+
+/// inverse of [`format_score`], `words` start after `score`
+pub fn parse_score<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<i32> {
+    let kind = words.next()?;
+    let n = words.next()?.parse::<i32>().ok()?;
+    match kind {
+        "cp" => Some(n),
+        "mate" if n > 0 => Some(MATE - (2 * n - 1)),
+        "mate" => Some(-MATE - 2 * n),
+        _ => None,
+    }
+}
+
+pub fn format_info(info: &SearchInfo, hashfull: usize) -> String {
+    let ms = info.time.as_millis().max(1);
+    let nps = info.nodes as u128 * 1000 / ms;
+    let pv = info
+        .pv
+        .iter()
+        .map(|mv| mv.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} nps {nps} hashfull {hashfull} time {} pv {pv}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        format_score(info.score),
+        info.nodes,
+        info.time.as_millis(),
+    )
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(out)),
+
+            board: Board::starting(),
+            side: Side::White,
+
+            search: Some(Search::new(SearchOptions::default())),
+            searching: None,
+
+            own_book: false,
+            book_file: DEFAULT_BOOK_FILE.to_string(),
+            book: None,
+
+            limit_strength: false,
+            elo: MAX_ELO,
+        }
+    }
+
+    pub fn run(&mut self, input: impl BufRead) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.command(&line?)? {
+                break;
+            }
+        }
+        self.wait();
+        Ok(())
+    }
+
+    /// handles a single command line, `false` after `quit`
+    pub fn command(&mut self, line: &str) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("uci") => self.uci()?,
+            Some("isready") => self.send("readyok")?,
+            Some("setoption") => self.set_option(words.collect::<Vec<_>>().join(" ")),
+            Some("ucinewgame") => self.search().clear(),
+            Some("position") => self.position(words)?,
+            Some("go") => self.go(words),
+            Some("stop") => self.stop(),
+            Some("ponderhit") => self.ponder_hit(),
+            Some("quit") => {
+                self.stop();
+                return Ok(false);
+            }
+            Some(other) => log::warn!("unknown command '{other}'"),
+            None => {}
+        }
+        Ok(true)
+    }
+
+    fn uci(&mut self) -> io::Result<()> {
+        self.send(&format!("id name {NAME}"))?;
+        self.send(&format!("id author {AUTHOR}"))?;
+
+        self.send("option name Threads type spin default 1 min 1 max 256")?;
+        self.send("option name Hash type spin default 16 min 1 max 65536")?;
+        self.send("option name MultiPV type spin default 1 min 1 max 256")?;
+        self.send("option name Clear Hash type button")?;
+        self.send("option name Ponder type check default false")?;
+        self.send("option name OwnBook type check default false")?;
+        self.send(&format!(
+            "option name BookFile type string default {DEFAULT_BOOK_FILE}"
+        ))?;
+        self.send("option name SyzygyPath type string default <empty>")?;
+        self.send("option name UCI_LimitStrength type check default false")?;
+        self.send(&format!(
+            "option name UCI_Elo type spin default {MAX_ELO} min {MIN_ELO} max {MAX_ELO}"
+        ))?;
+        for name in Techniques::NAMES {
+            self.send(&format!("option name {name} type check default true"))?;
+        }
+
+        self.send("uciok")
+    }
+
+    fn set_option(&mut self, args: String) {
+        // setoption name <id> [value <x>]
+        let args = args.strip_prefix("name ").unwrap_or(&args);
+        let (name, value) = match args.split_once(" value ") {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (args.trim(), ""),
+        };
+
+        match name {
+            "OwnBook" => {
+                self.own_book = value == "true";
+                self.load_book();
+                return;
+            }
+            "BookFile" => {
+                self.book_file = value.to_string();
+                self.book = None;
+                self.load_book();
+                return;
+            }
+            "UCI_LimitStrength" => {
+                self.limit_strength = value == "true";
+                self.update_strength();
+                return;
+            }
+            "UCI_Elo" => {
+                match value.parse::<u32>() {
+                    Ok(elo) => self.elo = elo.clamp(MIN_ELO, MAX_ELO),
+                    Err(_) => log::warn!("invalid UCI_Elo value '{value}'"),
+                }
+                self.update_strength();
+                return;
+            }
+            _ => {}
+        }
+
+        let search = self.search();
+        match name {
+            "Threads" => match value.parse() {
+                Ok(threads) => search.set_threads(threads),
+                Err(_) => log::warn!("invalid Threads value '{value}'"),
+            },
+            "Hash" => match value.parse() {
+                Ok(hash) => search.set_hash(hash),
+                Err(_) => log::warn!("invalid Hash value '{value}'"),
+            },
+            "MultiPV" => match value.parse() {
+                Ok(multi_pv) => search.set_multi_pv(multi_pv),
+                Err(_) => log::warn!("invalid MultiPV value '{value}'"),
+            },
+            "Clear Hash" => search.clear(),
+            "SyzygyPath" => {
+                let tablebase = match value {
+                    "" | "<empty>" => None,
+                    // several directories are separated like in PATH
+                    paths => {
+                        let mut tablebase = Tablebase::new();
+                        for path in std::env::split_paths(paths) {
+                            if let Err(err) = tablebase.add_directory(&path) {
+                                log::warn!(
+                                    "could not read tables from '{}': {err}",
+                                    path.display()
+                                );
+                            }
+                        }
+                        Some(Arc::new(tablebase))
+                    }
+                };
+                search.set_tablebase(tablebase);
+            }
+            // pondering is driven by the gui with `go ponder`
+            "Ponder" => {}
+            _ => {
+                let mut techniques = search.options().techniques;
+                if techniques.set(name, value == "true") {
+                    search.set_techniques(techniques);
+                } else {
+                    log::warn!("unknown option '{name}'");
+                }
+            }
+        }
+    }
+
+    /// sets up the position, an illegal move rejects the whole command and
+    /// keeps the previous position
+    fn position<'a>(&mut self, mut words: impl Iterator<Item = &'a str>) -> io::Result<()> {
+        let (mut board, mut side) = match words.next() {
+            Some("startpos") => {
+                words.next(); // moves
+                (Board::starting(), Side::White)
+            }
+            Some("fen") => {
+                let fen = words
+                    .by_ref()
+                    .take_while(|&word| word != "moves")
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match Board::from_fen(&fen) {
+                    Ok(ok) => ok,
+                    Err(err) => {
+                        log::warn!("invalid fen '{fen}': {err}");
+                        return Ok(());
+                    }
+                }
+            }
+            _ => {
+                log::warn!("invalid position command");
+                return Ok(());
+            }
+        };
+
+        for word in words {
+            match Move::parse(word).filter(|mv| board.legal_moves(side).contains(mv)) {
+                Some(mv) => {
+                    board.play(mv);
+                    side = side.other();
+                }
+                None => {
+                    log::error!("illegal move '{word}', position not changed");
+                    return self.send(&format!(
+                        "info string illegal move {word}, position not changed"
+                    ));
+                }
+            }
+        }
+        self.board = board;
+        self.side = side;
+        Ok(())
+    }
+
+    fn go<'a>(&mut self, words: impl Iterator<Item = &'a str>) {
+        let go = parse_go(words, self.side);
+        // an infinite or pondering search would never give its search back
+        self.stop();
+
+        if self.own_book && !go.ponder && !go.infinite {
+            let book_move = self
+                .book
+                .as_ref()
+                .and_then(|book| book.pick(&self.board, self.side, Selection::Weighted));
+            if let Some(mv) = book_move {
+                let _ = self.send(&format!("bestmove {mv}"));
+                return;
+            }
+        }
+
+        let search = self.search.take().expect("no idle search after stop");
+        let stop = Stop::new();
+        let pondering = Pondering::new(go.ponder);
+        let board = self.board.clone();
+        let side = self.side;
+        let out = self.out.clone();
+
+        let thread_stop = stop.clone();
+        let thread_pondering = pondering.clone();
+        let handle = thread::spawn(move || {
+            let result = search.go_ponder(
+                &board,
+                side,
+                go.limits,
+                thread_stop.clone(),
+                thread_pondering.clone(),
+                |info| {
+                    let line = format_info(info, search.hashfull());
+                    let _ = writeln!(out.lock().unwrap(), "{line}");
+                },
+            );
+
+            // infinite and ponder searches report only after being told to
+            // stop or getting a ponder hit
+            while (go.infinite || thread_pondering.is_pondering()) && !thread_stop.is_stopped() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let best = result
+                .best
+                .map_or_else(|| "0000".to_string(), |mv| mv.to_string());
+            let line = match result.pv.get(1) {
+                Some(ponder) => format!("bestmove {best} ponder {ponder}"),
+                None => format!("bestmove {best}"),
+            };
+            let mut out = out.lock().unwrap();
+            let _ = writeln!(out, "{line}");
+            let _ = out.flush();
+
+            search
+        });
+
+        self.searching = Some((handle, stop, pondering));
+    }
+
+    fn stop(&mut self) {
+        if let Some((_, stop, _)) = self.searching.as_ref() {
+            stop.stop();
+        }
+        self.wait();
+    }
+
+    /// the opponent played the ponder move, the search keeps going on its
+    /// own time now
+    fn ponder_hit(&mut self) {
+        match self.searching.as_ref() {
+            Some((_, _, pondering)) => pondering.hit(),
+            None => log::warn!("ponderhit without a search"),
+        }
+    }
+
+    fn load_book(&mut self) {
+        if !self.own_book || self.book.is_some() {
+            return;
+        }
+        match Book::open(&self.book_file) {
+            Ok(book) => self.book = Some(book),
+            Err(err) => log::warn!("could not open book '{}': {err}", self.book_file),
+        }
+    }
+
+    fn update_strength(&mut self) {
+        let strength = if self.limit_strength {
+            Strength::from_elo(self.elo)
+        } else {
+            Strength::FULL
+        };
+        self.search().set_strength(strength);
+    }
+
+    /// waits for a running search to finish on its own
+    fn wait(&mut self) {
+        if let Some((handle, _, _)) = self.searching.take() {
+            self.search = Some(handle.join().expect("search thread panicked"));
+        }
+    }
+
+    /// the idle search, a running one is stopped first
+    fn search(&mut self) -> &mut Search {
+        self.stop();
+        self.search.as_mut().unwrap()
+    }
+
+    fn send(&self, line: &str) -> io::Result<()> {
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{line}")?;
+        out.flush()
+    }
+}
+
+pub fn parse_go<'a>(mut words: impl Iterator<Item = &'a str>, side: Side) -> Go {
+    let mut go = Go::default();
+    let limits = &mut go.limits;
+
+    let ms = |word: Option<&str>| {
+        word.and_then(|word| word.parse::<i64>().ok())
+            .map(|ms| Duration::from_millis(ms.max(0) as u64))
+    };
+
+    while let Some(word) = words.next() {
+        match (word, side) {
+            ("wtime", Side::White) | ("btime", Side::Black) => limits.time = ms(words.next()),
+            ("winc", Side::White) | ("binc", Side::Black) => {
+                limits.increment = ms(words.next()).unwrap_or_default()
+            }
+            ("wtime" | "btime" | "winc" | "binc", _) => {
+                words.next();
+            }
+            ("movestogo", _) => limits.moves_to_go = words.next().and_then(|n| n.parse().ok()),
+            ("depth", _) => limits.depth = words.next().and_then(|n| n.parse().ok()),
+            ("nodes", _) => limits.nodes = words.next().and_then(|n| n.parse().ok()),
+            ("movetime", _) => limits.move_time = ms(words.next()),
+            ("infinite", _) => go.infinite = true,
+            ("ponder", _) => go.ponder = true,
+            _ => log::warn!("unknown go parameter '{word}'"),
+        }
+    }
+
+    go
+}
+
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// output the test can still read after the engine took it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(input: &str) -> Vec<String> {
+        let out = Shared::default();
+        Uci::new(out.clone()).run(input.as_bytes()).unwrap();
+        let out = out.0.lock().unwrap();
+        String::from_utf8_lossy(&out)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn bestmoves(lines: &[String]) -> usize {
+        lines
+            .iter()
+            .filter(|line| line.starts_with("bestmove"))
+            .count()
+    }
+
+    #[test]
+    fn commands_during_infinite_search() {
+        let lines = run("position startpos\n\
+             go infinite\n\
+             setoption name MultiPV value 2\n\
+             go ponder depth 2\n\
+             ucinewgame\n\
+             go depth 2\n");
+        assert_eq!(bestmoves(&lines), 3);
+    }
+
+    #[test]
+    fn positions_with_castling_and_promotion() {
+        let out = Shared::default();
+        let mut uci = Uci::new(out.clone());
+        let mut position = |line: &str| {
+            uci.command(line).unwrap();
+            uci.board.to_fen(uci.side)
+        };
+
+        assert_eq!(
+            position("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1"),
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4"
+        );
+        assert_eq!(
+            position("position fen 4k3/8/8/8/4p3/8/3P4/4K3 w - - 0 1 moves d2d4 e4d3"),
+            "4k3/8/8/8/8/3p4/8/4K3 w - - 0 2"
+        );
+        assert_eq!(
+            position("position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1 moves a7a8q"),
+            "Q7/7k/8/8/8/8/8/K7 b - - 0 1"
+        );
+
+        // the king cannot castle out of check, nothing is played
+        assert_eq!(
+            position("position fen 4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1 moves e1c1"),
+            "Q7/7k/8/8/8/8/8/K7 b - - 0 1"
+        );
+        let out = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        assert!(out.contains("info string illegal move e1c1"));
+    }
+
+    #[test]
+    fn finite_search_finishes_at_the_end_of_input() {
+        let lines = run("position startpos moves e2e4\ngo depth 2\n");
+        assert_eq!(bestmoves(&lines), 1);
+        assert!(lines.iter().any(|line| line.starts_with("info depth 2")));
+    }
+}
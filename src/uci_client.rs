@@ -0,0 +1,539 @@
+use crate::{board::Move, piece::Side, search::SearchInfo, time::Limits, uci::parse_score, Board};
+use std::{
+    ffi::OsStr,
+    io::{self, BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+//
+
+/// uci protocol gui side, drives an engine executable over its stdin and
+/// stdout
+///
+/// a background thread reads the engine output so [`UciEngine::poll`] never
+/// blocks the caller
+pub struct UciEngine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: String,
+    /// `option name` of every option the engine announced
+    options: Vec<String>,
+    thinking: bool,
+    /// latest `info` of every multi pv line of the running or last search
+    infos: Vec<SearchInfo>,
+}
+
+/// the answer to a `go`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BestMove {
+    /// `None` when the engine has no legal move (`bestmove 0000`)
+    pub mv: Option<Move>,
+    pub ponder: Option<Move>,
+}
+
+//
+
+/// how long the engine gets to answer `uci` and `isready`
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+//
+
+impl UciEngine {
+    /// starts the engine and waits for `uciok`
+    pub fn spawn(path: impl AsRef<OsStr>) -> io::Result<Self> {
+        let mut child = Command::new(path.as_ref())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = child.stdout.take().expect("piped stdout");
+
+        let (sender, lines) = mpsc::channel();
+        thread::Builder::new()
+            .name("uci client".to_string())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        let mut engine = Self {
+            child,
+            stdin,
+            lines,
+            name: path.as_ref().to_string_lossy().into_owned(),
+            options: Vec::new(),
+            thinking: false,
+            infos: Vec::new(),
+        };
+        engine.send("uci")?;
+        engine.wait_for("uciok")?;
+        Ok(engine)
+    }
+
+    /// `id name` of the engine, the path if it did not send one
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// names of the options the engine supports
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.thinking
+    }
+
+    /// `setoption`, only while idle, options the engine did not announce are
+    /// skipped
+    pub fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        if !self
+            .options
+            .iter()
+            .any(|option| option.eq_ignore_ascii_case(name))
+        {
+            log::warn!("{} has no option '{name}'", self.name);
+            return Ok(());
+        }
+        self.stop()?;
+        if value.is_empty() {
+            self.send(&format!("setoption name {name}"))
+        } else {
+            self.send(&format!("setoption name {name} value {value}"))
+        }
+    }
+
+    /// forgets the previous game and waits until the engine is ready
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.stop()?;
+        self.send("ucinewgame")?;
+        self.is_ready()
+    }
+
+    /// `isready`, waits for `readyok`
+    pub fn is_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        self.wait_for("readyok")
+    }
+
+    /// starts searching the position after `moves` from `start` with `side`
+    /// to move there, the result comes from [`UciEngine::poll`]
+    ///
+    /// the clock in `limits` is sent as the time of the side to move,
+    /// `opponent_time` as the other one if there is a clock for it
+    pub fn go(
+        &mut self,
+        start: &Board,
+        side: Side,
+        moves: &[Move],
+        limits: Limits,
+        opponent_time: Option<Duration>,
+    ) -> io::Result<()> {
+        let to_move = if moves.len().is_multiple_of(2) {
+            side
+        } else {
+            side.other()
+        };
+        let go = go_command(limits, to_move, opponent_time);
+        self.start(&position_command(start, side, moves), &go)
+    }
+
+    /// starts an infinite search of the position after `moves` to follow
+    /// with [`UciEngine::infos`], ended by [`UciEngine::stop`] or the next
+    /// search
+    pub fn analyse(&mut self, start: &Board, side: Side, moves: &[Move]) -> io::Result<()> {
+        self.start(&position_command(start, side, moves), "go infinite")
+    }
+
+    /// the best move once the engine is done, `None` while it still thinks
+    pub fn poll(&mut self) -> io::Result<Option<BestMove>> {
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => return Err(disconnected()),
+            };
+            if let Some(best) = self.handle(&line) {
+                return Ok(Some(best));
+            }
+        }
+    }
+
+    /// latest report of every line, best first, read by [`UciEngine::poll`]
+    pub fn infos(&self) -> &[SearchInfo] {
+        &self.infos
+    }
+
+    /// stops a running search and throws its result away
+    pub fn stop(&mut self) -> io::Result<()> {
+        if !self.thinking {
+            return Ok(());
+        }
+        self.send("stop")?;
+        while self.thinking {
+            let line = self.recv(HANDSHAKE_TIMEOUT)?;
+            self.handle(&line);
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, position: &str, go: &str) -> io::Result<()> {
+        self.stop()?;
+        self.infos.clear();
+        self.send(position)?;
+        self.send(go)?;
+        self.thinking = true;
+        Ok(())
+    }
+
+    fn handle(&mut self, line: &str) -> Option<BestMove> {
+        log::trace!("< {line}");
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("id") if words.next() == Some("name") => {
+                self.name = words.collect::<Vec<_>>().join(" ");
+            }
+            Some("option") if words.next() == Some("name") => {
+                let name = words.take_while(|&word| word != "type");
+                self.options.push(name.collect::<Vec<_>>().join(" "));
+            }
+            Some("info") if self.thinking => {
+                if let Some(info) = parse_info(words) {
+                    let i = info.multipv.max(1) - 1;
+                    if i >= self.infos.len() {
+                        self.infos.resize(i + 1, info.clone());
+                    }
+                    self.infos[i] = info;
+                }
+            }
+            Some("bestmove") if self.thinking => {
+                self.thinking = false;
+                let mv = words.next().and_then(Move::parse);
+                let ponder = match words.next() {
+                    Some("ponder") => words.next().and_then(Move::parse),
+                    _ => None,
+                };
+                return Some(BestMove { mv, ponder });
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// reads lines until `token`
+    fn wait_for(&mut self, token: &str) -> io::Result<()> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = self.recv(deadline.saturating_duration_since(Instant::now()))?;
+            if line.trim() == token {
+                return Ok(());
+            }
+            self.handle(&line);
+        }
+    }
+
+    fn recv(&mut self, timeout: Duration) -> io::Result<String> {
+        self.lines.recv_timeout(timeout).map_err(|err| match err {
+            RecvTimeoutError::Timeout => {
+                io::Error::new(io::ErrorKind::TimedOut, "engine did not answer")
+            }
+            RecvTimeoutError::Disconnected => disconnected(),
+        })
+    }
+
+    fn send(&mut self, line: &str) -> io::Result<()> {
+        log::trace!("> {line}");
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // give it a moment to exit on its own
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+//
+
+/// an `info` line with a score, `words` start after `info`
+///
+/// fields the engine left out are 0, `info string` and `currmove` updates
+/// are `None`
+pub fn parse_info<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<SearchInfo> {
+    let mut info = SearchInfo {
+        multipv: 1,
+        depth: 0,
+        seldepth: 0,
+        score: 0,
+        nodes: 0,
+        time: Duration::ZERO,
+        pv: Vec::new(),
+    };
+    let mut scored = false;
+
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => info.depth = number(words.next())?,
+            "seldepth" => info.seldepth = number(words.next())?,
+            "multipv" => info.multipv = number(words.next())?,
+            "nodes" => info.nodes = number(words.next())?,
+            "time" => info.time = Duration::from_millis(number(words.next())?),
+            "score" => {
+                info.score = parse_score(words.by_ref())?;
+                scored = true;
+            }
+            // the rest of the line is the pv
+            "pv" => info.pv = words.by_ref().map_while(Move::parse).collect(),
+            "string" => return None,
+            _ => {}
+        }
+    }
+
+    scored.then_some(info)
+}
+
+/// the `position` command, the moves since `start` are sent too so the
+/// engine knows castling rights and repetitions
+pub fn position_command(start: &Board, side: Side, moves: &[Move]) -> String {
+    let mut position = if side == Side::White && *start == Board::starting() {
+        "position startpos".to_string()
+    } else {
+        format!("position fen {}", start.to_fen(side))
+    };
+    if !moves.is_empty() {
+        position += " moves";
+        for mv in moves {
+            position += &format!(" {mv}");
+        }
+    }
+    position
+}
+
+/// the `go` command for `limits`, an infinite search without any
+pub fn go_command(limits: Limits, side: Side, opponent_time: Option<Duration>) -> String {
+    let (time, opponent, inc, opponent_inc) = match side {
+        Side::White => ("wtime", "btime", "winc", "binc"),
+        Side::Black => ("btime", "wtime", "binc", "winc"),
+    };
+
+    let mut go = "go".to_string();
+    if let Some(clock) = limits.time {
+        go += &format!(" {time} {}", clock.as_millis());
+        if let Some(clock) = opponent_time {
+            go += &format!(" {opponent} {}", clock.as_millis());
+        }
+        if !limits.increment.is_zero() {
+            // both sides play with the same increment
+            go += &format!(
+                " {inc} {ms} {opponent_inc} {ms}",
+                ms = limits.increment.as_millis()
+            );
+        }
+        if let Some(moves_to_go) = limits.moves_to_go {
+            go += &format!(" movestogo {moves_to_go}");
+        }
+    }
+    if let Some(move_time) = limits.move_time {
+        go += &format!(" movetime {}", move_time.as_millis());
+    }
+    if let Some(depth) = limits.depth {
+        go += &format!(" depth {depth}");
+    }
+    if let Some(nodes) = limits.nodes {
+        go += &format!(" nodes {nodes}");
+    }
+    if go == "go" {
+        go += " infinite";
+    }
+    go
+}
+
+fn number<T: std::str::FromStr>(word: Option<&str>) -> Option<T> {
+    word?.parse().ok()
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "engine exited")
+}
+
+//
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    /// answers the handshake, and every `go` with a move that depends on
+    /// the last position
+    const STUB: &str = r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci)
+            echo "id name stub engine"
+            echo "option name Hash type spin default 16 min 1 max 64"
+            echo "option name Skill Level type spin default 20 min 0 max 20"
+            echo "uciok" ;;
+        isready) echo "readyok" ;;
+        position*) position="$line" ;;
+        go*)
+            echo "info string thinking"
+            echo "info depth 3 seldepth 5 multipv 1 score cp 25 nodes 100 time 7 pv e2e4 e7e5"
+            case "$position" in
+                "position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1")
+                    echo "bestmove f8c5" ;;
+                "position fen 8/P6k/8/8/8/8/8/K7 w - - 0 1")
+                    echo "bestmove a7a8q" ;;
+                *) echo "bestmove e2e4 ponder e7e5" ;;
+            esac ;;
+        quit) exit 0 ;;
+    esac
+done
+"#;
+
+    /// writes the stub to a temp file of its own for every `name`
+    #[cfg(unix)]
+    pub(crate) fn stub(name: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path =
+            std::env::temp_dir().join(format!("chess-uci-stub-{name}-{}", std::process::id()));
+        fs::write(&path, STUB).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn mv(s: &str) -> Move {
+        Move::parse(s).unwrap()
+    }
+
+    fn best_move(engine: &mut UciEngine) -> BestMove {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            if let Some(best) = engine.poll().unwrap() {
+                return best;
+            }
+            assert!(Instant::now() < deadline, "no bestmove");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn plays_against_a_stub() {
+        let path = stub("client");
+        let mut engine = UciEngine::spawn(&path).unwrap();
+        assert_eq!(engine.name(), "stub engine");
+        assert_eq!(engine.options(), ["Hash", "Skill Level"]);
+        engine.set_option("Skill Level", "3").unwrap();
+        engine.new_game().unwrap();
+
+        let board = Board::starting();
+        engine
+            .go(&board, Side::White, &[], Limits::depth(3), None)
+            .unwrap();
+        assert!(engine.is_thinking());
+        assert_eq!(
+            best_move(&mut engine),
+            BestMove {
+                mv: Some(mv("e2e4")),
+                ponder: Some(mv("e7e5")),
+            }
+        );
+        assert!(!engine.is_thinking());
+        assert_eq!(engine.infos().len(), 1);
+        assert_eq!(engine.infos()[0].score, 25);
+        assert_eq!(engine.infos()[0].pv, [mv("e2e4"), mv("e7e5")]);
+
+        // the history reaches the engine, castling included
+        let moves = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"].map(mv);
+        engine
+            .go(&board, Side::White, &moves, Limits::depth(3), None)
+            .unwrap();
+        assert_eq!(best_move(&mut engine).mv, Some(mv("f8c5")));
+
+        let (board, side) = Board::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        engine
+            .go(&board, side, &[], Limits::depth(3), None)
+            .unwrap();
+        let promotion = best_move(&mut engine).mv.unwrap();
+        assert_eq!(promotion.promotion, Some(crate::piece::Piece::Queen));
+        assert!(board.legal_moves(side).contains(&promotion));
+
+        drop(engine);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn position_commands() {
+        let start = Board::starting();
+        assert_eq!(
+            position_command(&start, Side::White, &[]),
+            "position startpos"
+        );
+        assert_eq!(
+            position_command(&start, Side::White, &[mv("e2e4"), mv("e7e5")]),
+            "position startpos moves e2e4 e7e5"
+        );
+        let (board, side) = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            position_command(&board, side, &[mv("a7a8q")]),
+            "position fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q"
+        );
+    }
+
+    #[test]
+    fn parses_info_lines() {
+        let info = parse_info(
+            "depth 12 seldepth 20 multipv 2 score mate 3 nodes 5000 time 250 pv d1d8 g8h7"
+                .split_whitespace(),
+        )
+        .unwrap();
+        assert_eq!(info.depth, 12);
+        assert_eq!(info.seldepth, 20);
+        assert_eq!(info.multipv, 2);
+        assert_eq!(info.score, crate::eval::MATE - 5);
+        assert_eq!(info.nodes, 5000);
+        assert_eq!(info.time, Duration::from_millis(250));
+        assert_eq!(info.pv, [mv("d1d8"), mv("g8h7")]);
+
+        assert_eq!(parse_info("string hello".split_whitespace()), None);
+        assert_eq!(parse_info("depth 3 currmove e2e4".split_whitespace()), None);
+    }
+
+    #[test]
+    fn go_commands() {
+        assert_eq!(
+            go_command(Limits::depth(5), Side::White, None),
+            "go depth 5"
+        );
+        assert_eq!(
+            go_command(Limits::default(), Side::White, None),
+            "go infinite"
+        );
+
+        let limits = Limits::clock(Duration::from_secs(60), Duration::from_secs(2), Some(10));
+        assert_eq!(
+            go_command(limits, Side::Black, Some(Duration::from_secs(30))),
+            "go btime 60000 wtime 30000 binc 2000 winc 2000 movestogo 10"
+        );
+    }
+}